@@ -0,0 +1,164 @@
+use crate::config::Config;
+use crate::network::Chain;
+use bitcoincore_rpc::jsonrpc::{self, simple_http};
+use bitcoincore_rpc::{Auth, Client};
+use std::path::{Path, PathBuf};
+use std::{env, fmt};
+
+// Credentials used when neither a cookie nor a user/password is configured.
+// These match the `rpcauth` entry of the provided docker-compose node.
+const DEFAULT_RPC_USER: &str = "alice";
+const DEFAULT_RPC_PASS: &str = "password";
+
+/// How we authenticate against the node. Kept around so a failed connection
+/// can tell the operator which credentials were rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthMethod {
+    /// `.cookie` file written by bitcoind, either configured or discovered in the datadir.
+    Cookie(PathBuf),
+    /// Plain `rpcuser`/`rpcpassword`, or an `rpcauth` entry's user and password.
+    UserPass { user: String, explicit: bool },
+}
+
+impl fmt::Display for AuthMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthMethod::Cookie(path) => write!(f, "cookie file {}", path.display()),
+            AuthMethod::UserPass { user, explicit } => {
+                let source = if *explicit { "configured" } else { "default" };
+                write!(f, "{source} user/password for {user:?}")
+            }
+        }
+    }
+}
+
+/// A `.cookie` found in the datadir that couldn't be read, so resolution moved on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedCookie {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for SkippedCookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ignored cookie file {}: {}",
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+/// Credentials resolved from the config: what to send and how we got it.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub method: AuthMethod,
+    /// Set when the built-in user/password is used because the discovered
+    /// cookie was unreadable. Callers decide whether to tell the operator.
+    pub skipped_cookie: Option<SkippedCookie>,
+    user: String,
+    pass: String,
+}
+
+impl Credentials {
+    /// Picks credentials in this order: an explicit cookie file, an explicit
+    /// user/password, a `.cookie` found in the datadir for the configured
+    /// network (or any network, if none is configured), then the built-in
+    /// user/password.
+    pub fn resolve(config: &Config) -> bitcoincore_rpc::Result<Credentials> {
+        if let Some(path) = &config.rpc_cookie {
+            return Credentials::from_cookie(path);
+        }
+
+        if config.rpc_user.is_some() || config.rpc_pass.is_some() {
+            let user = config.rpc_user.clone().unwrap_or_default();
+            return Ok(Credentials {
+                method: AuthMethod::UserPass {
+                    user: user.clone(),
+                    explicit: true,
+                },
+                skipped_cookie: None,
+                user,
+                pass: config.rpc_pass.clone().unwrap_or_default(),
+            });
+        }
+
+        let chains = match config.network {
+            Some(chain) => vec![chain],
+            None => Chain::ALL.to_vec(),
+        };
+        let discovered = config
+            .datadir
+            .clone()
+            .or_else(default_datadir)
+            .and_then(|datadir| {
+                chains
+                    .into_iter()
+                    .map(|chain| datadir.join(chain.datadir_subdir()).join(".cookie"))
+                    .find(|path| path.is_file())
+            });
+        // An unreadable cookie (e.g. owned by another user) is not fatal as long
+        // as user/password still works.
+        let skipped_cookie = match discovered {
+            Some(path) => match Credentials::from_cookie(&path) {
+                Ok(credentials) => return Ok(credentials),
+                Err(e) => Some(SkippedCookie {
+                    path,
+                    reason: e.to_string(),
+                }),
+            },
+            None => None,
+        };
+
+        Ok(Credentials {
+            method: AuthMethod::UserPass {
+                user: DEFAULT_RPC_USER.to_owned(),
+                explicit: false,
+            },
+            skipped_cookie,
+            user: DEFAULT_RPC_USER.to_owned(),
+            pass: DEFAULT_RPC_PASS.to_owned(),
+        })
+    }
+
+    fn from_cookie(path: &Path) -> bitcoincore_rpc::Result<Credentials> {
+        let (user, pass) = Auth::CookieFile(path.to_owned()).get_user_pass()?;
+        Ok(Credentials {
+            method: AuthMethod::Cookie(path.to_owned()),
+            skipped_cookie: None,
+            user: user.unwrap_or_default(),
+            pass: pass.unwrap_or_default(),
+        })
+    }
+
+    pub fn auth(&self) -> Auth {
+        Auth::UserPass(self.user.clone(), self.pass.clone())
+    }
+
+    /// Opens a client for `url` with these credentials.
+    pub fn connect(&self, url: &str) -> bitcoincore_rpc::Result<Client> {
+        Client::new(url, self.auth())
+    }
+}
+
+/// Returns true if the RPC call failed because the node refused our credentials.
+pub fn is_unauthorized(error: &bitcoincore_rpc::Error) -> bool {
+    match error {
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(e)) => matches!(
+            e.downcast_ref::<simple_http::Error>(),
+            Some(simple_http::Error::HttpErrorCode(401))
+        ),
+        _ => false,
+    }
+}
+
+// bitcoind's default datadir on this platform.
+fn default_datadir() -> Option<PathBuf> {
+    let home = PathBuf::from(env::var_os("HOME")?);
+    if cfg!(target_os = "macos") {
+        Some(home.join("Library/Application Support/Bitcoin"))
+    } else {
+        Some(home.join(".bitcoin"))
+    }
+}
@@ -0,0 +1,356 @@
+use crate::error::Result;
+use crate::node::Node;
+use crate::wallet::{ListedDescriptor, WalletHandle, WalletSpec};
+use bitcoincore_rpc::bitcoin::Amount;
+use bitcoincore_rpc::RpcApi;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fmt, fs};
+
+/// Value of [`Backup::format`].
+pub const FORMAT: &str = "capstone-wallet-backup";
+/// Version of the backup file layout this build writes and reads.
+pub const VERSION: u32 = 1;
+
+/// Why a backup could not be read or restored, or didn't restore faithfully.
+#[derive(Debug)]
+pub enum BackupError {
+    /// The file is not JSON in the backup layout.
+    Parse(serde_json::Error),
+    /// A different `format`, or a `version` this build doesn't know.
+    Unsupported { format: String, version: u32 },
+    /// The restored wallet doesn't hold what the backup recorded.
+    Mismatch {
+        wallet: String,
+        verification: Verification,
+    },
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::Parse(e) => write!(f, "invalid backup file: {e}"),
+            BackupError::Unsupported { format, version } => {
+                write!(f, "unsupported backup {format:?} version {version}")
+            }
+            BackupError::Mismatch {
+                wallet,
+                verification,
+            } => write!(
+                f,
+                "restored wallet {wallet} does not match its backup: {verification}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+/// A wallet's `getbalances` (`mine`), in BTC on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balances {
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub trusted: Amount,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub untrusted_pending: Amount,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub immature: Amount,
+}
+
+impl fmt::Display for Balances {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "trusted {}, pending {}, immature {}",
+            self.trusted, self.untrusted_pending, self.immature
+        )
+    }
+}
+
+/// One descriptor as `listdescriptors` lists it and `importdescriptors` takes it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupDescriptor {
+    /// With private keys when the wallet has them.
+    pub desc: String,
+    /// Creation time; restoring rescans blocks from then on.
+    pub timestamp: u64,
+    pub active: bool,
+    #[serde(default)]
+    pub internal: bool,
+    /// Derivation range of ranged descriptors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<[u64; 2]>,
+    /// Next index to hand out, so restored wallets don't reuse addresses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_index: Option<u64>,
+}
+
+impl From<ListedDescriptor> for BackupDescriptor {
+    fn from(d: ListedDescriptor) -> BackupDescriptor {
+        BackupDescriptor {
+            desc: d.desc,
+            timestamp: d.timestamp,
+            active: d.active,
+            internal: d.internal,
+            range: d.range,
+            next_index: d.next_index.or(d.next),
+        }
+    }
+}
+
+/// A portable copy of a descriptor wallet: its descriptors, the labels of its
+/// addresses, and the balances at backup time to verify a restore against.
+///
+/// Unlike a `backupwallet` file it doesn't depend on Core's database format, so
+/// it can be restored on any node running the same chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Backup {
+    pub format: String,
+    pub version: u32,
+    pub wallet: String,
+    /// Seconds since the epoch.
+    pub created: u64,
+    /// `false` for watch-only wallets, whose descriptors hold only public keys.
+    pub private_keys: bool,
+    pub descriptors: Vec<BackupDescriptor>,
+    /// Address to label; unlabelled addresses map to "".
+    pub labels: BTreeMap<String, String>,
+    pub balances: Balances,
+}
+
+/// What a restored wallet holds compared with its backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub expected: Balances,
+    pub actual: Balances,
+    /// Addresses in the backup the wallet doesn't know.
+    pub missing: Vec<String>,
+    /// Addresses the wallet knows that aren't in the backup.
+    pub extra: Vec<String>,
+}
+
+impl Verification {
+    pub fn matches(&self) -> bool {
+        self.expected == self.actual && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.expected == self.actual {
+            write!(f, "balances match ({})", self.actual)?;
+        } else {
+            write!(
+                f,
+                "balances differ (backup: {}; wallet: {})",
+                self.expected, self.actual
+            )?;
+        }
+        write!(
+            f,
+            ", {} address(es) missing, {} extra",
+            self.missing.len(),
+            self.extra.len()
+        )
+    }
+}
+
+fn balances(wallet: &WalletHandle) -> Result<Balances> {
+    let mine = wallet.client().get_balances()?.mine;
+    Ok(Balances {
+        trusted: mine.trusted,
+        untrusted_pending: mine.untrusted_pending,
+        immature: mine.immature,
+    })
+}
+
+// Every address in the wallet's address book with its label, from
+// `listreceivedbyaddress` including empty and watch-only ones.
+fn labels(wallet: &WalletHandle) -> Result<BTreeMap<String, String>> {
+    #[derive(Deserialize)]
+    struct Received {
+        address: String,
+        #[serde(default)]
+        label: String,
+    }
+    let received: Vec<Received> = wallet.client().call(
+        "listreceivedbyaddress",
+        &[0.into(), true.into(), true.into()],
+    )?;
+    Ok(received.into_iter().map(|r| (r.address, r.label)).collect())
+}
+
+/// Records `wallet`'s descriptors (`listdescriptors true`, or without private
+/// keys for watch-only wallets), address labels and balances.
+pub fn backup(wallet: &WalletHandle) -> Result<Backup> {
+    let (private_keys, descriptors) = match wallet.descriptors(true)? {
+        Some(descriptors) => (true, descriptors),
+        None => (false, wallet.descriptors(false)?.unwrap_or_default()),
+    };
+
+    Ok(Backup {
+        format: FORMAT.to_owned(),
+        version: VERSION,
+        wallet: wallet.name().to_owned(),
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        private_keys,
+        descriptors: descriptors.into_iter().map(Into::into).collect(),
+        labels: labels(wallet)?,
+        balances: balances(wallet)?,
+    })
+}
+
+impl Backup {
+    /// Writes the backup as pretty JSON to a new file only the owner can read,
+    /// since it holds private keys unless the wallet is watch-only. Never
+    /// overwrites an existing file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Backup> {
+        let text = fs::read_to_string(path)?;
+        let backup: Backup = serde_json::from_str(&text).map_err(BackupError::Parse)?;
+        if backup.format != FORMAT || backup.version != VERSION {
+            return Err(BackupError::Unsupported {
+                format: backup.format,
+                version: backup.version,
+            }
+            .into());
+        }
+        Ok(backup)
+    }
+}
+
+/// Creates `name` as a blank descriptor wallet and imports the backup into it:
+/// descriptors through `importdescriptors`, which rescans from each descriptor's
+/// timestamp (or from `rescan_from`, seconds since the epoch, when given), then
+/// the address labels. Finishes with [`verify`] and fails if the wallet doesn't
+/// match the backup.
+///
+/// The wallet is set up through [`Node::reconcile`], so if an earlier restore
+/// failed after creating it, running it again imports into that wallet.
+///
+/// With a passphrase source configured, a wallet with private keys is created
+/// encrypted and unlocked only for the import.
+pub fn restore(
+    node: &Node,
+    backup: &Backup,
+    name: &str,
+    rescan_from: Option<u64>,
+) -> Result<(WalletHandle, Verification)> {
+    let spec = WalletSpec::new()
+        .disable_private_keys(!backup.private_keys)
+        .blank(true);
+    let (wallet, outcome) = node.reconcile(name, Some(&spec))?;
+    outcome.matched(name)?;
+
+    let requests: Vec<Value> = backup
+        .descriptors
+        .iter()
+        .map(|d| {
+            let mut request = json!({
+                "desc": d.desc,
+                "timestamp": rescan_from.unwrap_or(d.timestamp),
+                "active": d.active,
+            });
+            if d.active {
+                request["internal"] = d.internal.into();
+            }
+            if let Some(range) = d.range {
+                request["range"] = json!(range);
+                if let Some(next) = d.next_index {
+                    request["next_index"] = next.into();
+                }
+            }
+            request
+        })
+        .collect();
+
+    let unlocked = node.unlock(&wallet)?;
+    wallet.import_descriptors(requests)?;
+    drop(unlocked);
+
+    for (address, label) in &backup.labels {
+        let _: Value = wallet.client().call(
+            "setlabel",
+            &[address.as_str().into(), label.as_str().into()],
+        )?;
+    }
+
+    let verification = verify(&wallet, backup)?;
+    if !verification.matches() {
+        return Err(BackupError::Mismatch {
+            wallet: name.to_owned(),
+            verification,
+        }
+        .into());
+    }
+    Ok((wallet, verification))
+}
+
+/// Has the node copy `wallet`'s database to `path` with `backupwallet`. The
+/// path is on the node's filesystem and the copy only opens in Core.
+pub fn backup_file(wallet: &WalletHandle, path: &str) -> Result<()> {
+    wallet.client().backup_wallet(Some(path))?;
+    Ok(())
+}
+
+/// Restores a `backupwallet` file with `restorewallet` and verifies it against
+/// `backup`, taken at the same time. `path` is on the node's filesystem.
+pub fn restore_file(
+    node: &Node,
+    backup: &Backup,
+    name: &str,
+    path: &str,
+) -> Result<(WalletHandle, Verification)> {
+    let _: Value = node
+        .rpc()
+        .call("restorewallet", &[name.into(), path.into()])?;
+    let wallet = node.loaded_wallet(name)?;
+    let verification = verify(&wallet, backup)?;
+    if !verification.matches() {
+        return Err(BackupError::Mismatch {
+            wallet: name.to_owned(),
+            verification,
+        }
+        .into());
+    }
+    Ok((wallet, verification))
+}
+
+/// Compares `wallet`'s balances and address book with what `backup` recorded.
+pub fn verify(wallet: &WalletHandle, backup: &Backup) -> Result<Verification> {
+    let known = labels(wallet)?;
+    let expected: BTreeSet<&String> = backup.labels.keys().collect();
+    let actual: BTreeSet<&String> = known.keys().collect();
+    Ok(Verification {
+        expected: backup.balances,
+        actual: balances(wallet)?,
+        missing: expected
+            .difference(&actual)
+            .map(|a| a.to_string())
+            .collect(),
+        extra: actual
+            .difference(&expected)
+            .map(|a| a.to_string())
+            .collect(),
+    })
+}
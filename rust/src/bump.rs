@@ -0,0 +1,145 @@
+use crate::error::{Error, Result};
+use crate::report::{csv_field, ReportFormat};
+use crate::wallet::WalletHandle;
+use bitcoincore_rpc::bitcoin::{Amount, Txid};
+use bitcoincore_rpc::RpcApi;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+/// A replacement broadcast by `bumpfee`.
+///
+/// Amounts serialize in BTC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeeBump {
+    /// The transaction that was stuck; it leaves the mempool.
+    pub replaced: Txid,
+    pub replacement: Txid,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub original_fee: Amount,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    /// How much more the replacement pays.
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub fee_delta: Amount,
+}
+
+/// An unsigned replacement from `psbtbumpfee`, for wallets that cannot sign it
+/// themselves. It continues through [`psbt::sign`](crate::psbt::sign).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BumpPsbt {
+    pub psbt: String,
+    pub original_fee: Amount,
+    pub fee: Amount,
+}
+
+#[derive(Deserialize)]
+struct RawBump {
+    txid: Option<Txid>,
+    psbt: Option<String>,
+    #[serde(
+        rename = "origfee",
+        with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc"
+    )]
+    original_fee: Amount,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    fee: Amount,
+}
+
+// Core only bumps to a higher fee, but don't panic on a reply that says otherwise.
+fn fee_delta(original_fee: Amount, fee: Amount) -> Amount {
+    fee.checked_sub(original_fee).unwrap_or(Amount::ZERO)
+}
+
+// Arguments of `bumpfee` and `psbtbumpfee`.
+fn bump_args(txid: &Txid, fee_rate: f64) -> [Value; 2] {
+    [txid.to_string().into(), json!({ "fee_rate": fee_rate })]
+}
+
+/// Replaces `txid`, an unconfirmed BIP 125 replaceable payment from `wallet`,
+/// with one paying `fee_rate` sat/vB, and broadcasts it. The recipients are paid
+/// the same; the extra fee comes out of the change.
+pub fn bump_fee(wallet: &WalletHandle, txid: &Txid, fee_rate: f64) -> Result<FeeBump> {
+    let bumped: RawBump = wallet
+        .client()
+        .call("bumpfee", &bump_args(txid, fee_rate))?;
+    let replacement = bumped
+        .txid
+        .ok_or(Error::Rpc(bitcoincore_rpc::Error::UnexpectedStructure))?;
+    Ok(FeeBump {
+        replaced: *txid,
+        replacement,
+        original_fee: bumped.original_fee,
+        fee: bumped.fee,
+        fee_delta: fee_delta(bumped.original_fee, bumped.fee),
+    })
+}
+
+/// Like [`bump_fee`], but returns the replacement as a PSBT instead of signing
+/// and broadcasting it.
+pub fn psbt_bump_fee(wallet: &WalletHandle, txid: &Txid, fee_rate: f64) -> Result<BumpPsbt> {
+    let bumped: RawBump = wallet
+        .client()
+        .call("psbtbumpfee", &bump_args(txid, fee_rate))?;
+    let psbt = bumped
+        .psbt
+        .ok_or(Error::Rpc(bitcoincore_rpc::Error::UnexpectedStructure))?;
+    Ok(BumpPsbt {
+        psbt,
+        original_fee: bumped.original_fee,
+        fee: bumped.fee,
+    })
+}
+
+impl FeeBump {
+    /// Writes the bump in `format`, laid out like a [`TransactionReport`](crate::TransactionReport).
+    pub fn write<W: Write>(&self, format: ReportFormat, mut out: W) -> Result<()> {
+        match format {
+            ReportFormat::Text => self.write_text(out)?,
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut out, self)?;
+                writeln!(out)?;
+            }
+            ReportFormat::Csv => self.write_csv(out)?,
+            ReportFormat::Markdown => self.write_markdown(out)?,
+        }
+        Ok(())
+    }
+
+    /// One field per line: replaced txid, replacement txid, original fee, new fee
+    /// and the difference, fees in BTC.
+    pub fn write_text<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for (_, value) in self.summary() {
+            writeln!(out, "{value}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes a header row and one data row.
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let summary = self.summary();
+        let header: Vec<&str> = summary.iter().map(|(name, _)| *name).collect();
+        let row: Vec<String> = summary.iter().map(|(_, value)| csv_field(value)).collect();
+        writeln!(out, "{}", header.join(","))?;
+        writeln!(out, "{}", row.join(","))
+    }
+
+    pub fn write_markdown<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "| Field | Value |")?;
+        writeln!(out, "| --- | --- |")?;
+        for (name, value) in self.summary() {
+            writeln!(out, "| {name} | {value} |")?;
+        }
+        Ok(())
+    }
+
+    fn summary(&self) -> [(&'static str, String); 5] {
+        [
+            ("replaced_txid", self.replaced.to_string()),
+            ("replacement_txid", self.replacement.to_string()),
+            ("original_fee", self.original_fee.to_btc().to_string()),
+            ("fee", self.fee.to_btc().to_string()),
+            ("fee_delta", self.fee_delta.to_btc().to_string()),
+        ]
+    }
+}
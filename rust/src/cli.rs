@@ -0,0 +1,1311 @@
+use bitcoincore_rpc::bitcoin::address::NetworkUnchecked;
+use bitcoincore_rpc::bitcoin::{Address, Amount, Denomination, Txid};
+use capstone::backup::{self, Backup};
+use capstone::scenario::Scenario;
+use capstone::send::{SendOptions, SendResult};
+use capstone::wallet::{WalletSpec, MINING_REWARD_LABEL, RECEIVED_LABEL};
+use capstone::{bump, cpfp, mempool, mining, multisig, psbt};
+use capstone::{workflow, Error, Node, Result, TransactionReport};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage: rust [CONFIG FLAGS] [COMMAND]
+
+Commands:
+  run                                    Run the whole Miner -> Trader flow and write the report (default)
+  wallet create <name> [--watch-only] [--blank] [--avoid-reuse] [--legacy] [--load-on-startup]
+                                         Create the wallet if needed and load it, printing whether
+                                         it was created, loaded or already loaded; an existing
+                                         wallet must have been set up with the same options
+  wallet load <name>                     Load an existing wallet
+  wallet list                            List wallets in the wallet directory
+  wallet labels <name>                   List the labels used by a wallet
+  wallet addresses <name> --label <label>
+                                         List a wallet's addresses carrying <label>
+  wallet backup <name> --out <file> [--db <node path>]
+                                         Write a portable backup: descriptors (with private keys),
+                                         labels and balances; --db also has the node copy its
+                                         wallet database there with backupwallet
+  wallet restore <file> [--as <name>] [--rescan-from <unix time>] [--db <node path>]
+                                         Recreate the wallet from a backup (or from the node-side
+                                         database copy with restorewallet) and verify it
+  wallet verify <name> <file>            Compare a wallet's balances and addresses with a backup
+  wallet encrypt <name>                  Encrypt a wallet with the passphrase from --passphrase-from
+  mine <n> [--to <wallet>]               Mine n blocks to a new address of <wallet> (default: Miner)
+  mine until-spendable [--to <wallet>]   Mine one block at a time until <wallet> can spend a block reward
+  send [--from <wallet>] [--to <wallet>] --amount <btc> [FEE FLAGS]
+                                         Pay <btc> from one wallet to a new address of another
+                                         (default: Miner to Trader) and print the fee rate paid
+  send-many [--from <wallet>] <address>=<btc>... [FEE FLAGS] [FUNDING FLAGS]
+                                         Pay several addresses in one transaction; prints the txid,
+                                         or the PSBT if the wallet could not sign every input
+  bump-fee <txid> --fee-rate <sat/vB> [--from <wallet>] [--psbt [--out <file>]]
+                                         Replace a stuck payment with one paying a higher fee rate
+                                         and report both txids and the fee delta; with --psbt,
+                                         write the unsigned replacement PSBT instead
+  cpfp <txid> --fee-rate <sat/vB> [--wallet <wallet>]
+                                         Spend <wallet>'s unconfirmed outputs of <txid> (default:
+                                         Trader) so the package pays <sat/vB>; prints the child
+  psbt create [--from <wallet>] <address>=<btc>... [--out <file>] [FEE FLAGS] [FUNDING FLAGS]
+                                         Fund an unsigned PSBT from <wallet> (default: Miner)
+  psbt inspect <file>                    Show a PSBT's outputs, fee and the next role to act on it
+  psbt sign <file> [--wallet <wallet>] [--out <file>]
+                                         Add <wallet>'s signatures (default: Miner)
+  psbt finalize <file> [--out <file>]    Finalize a signed PSBT into a transaction hex
+  psbt broadcast <file>                  Broadcast a finalized transaction hex
+  multisig create <name> --threshold <k> --cosigners <wallet>,<wallet>,...
+                                         Create <name>, a watch-only k-of-n wallet over the
+                                         cosigners' keys (cosigner wallets are created if missing)
+  multisig sign <file> --cosigners <wallet>,<wallet>,... [--out <file>]
+                                         Pass a PSBT through the cosigners until it is fully signed
+  mempool show <txid>                    Print the mempool entry of an unconfirmed transaction
+                                         with its in-mempool ancestors and descendants
+  mempool list                           Print every mempool entry
+  report <txid> [--from <wallet>]        Print the report of a confirmed payment sent by <wallet> (default: Miner)
+  scenario <file>                        Run the steps of a TOML scenario file and report each result
+  help                                   Print this message
+
+Fee flags (default: the node's estimate, or fallbackfee):
+  --fee-rate <sat/vB>, --conf-target <blocks>, --estimate-mode <economical|conservative>,
+  --subtract-fee (take the fee out of the amount sent), --replaceable (signal BIP 125)
+
+Funding flags (send-many, psbt create):
+  --change-address <address>, --change-position <n>, --locktime <n>,
+  --include-watching (also spend watch-only outputs)
+
+Config flags (see README): --config, --rpc-host, --rpc-port, --rpc-user, --rpc-pass,
+  --rpc-cookie, --datadir, --network, --miner-wallet, --trader-wallet, --output,
+  --format (text, json, csv, markdown), --passphrase-from (prompt, env:<VAR>, fd:<N>),
+  --unlock-timeout <secs>";
+
+/// A parsed subcommand. Wallet names left as `None` fall back to the configured ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Run,
+    WalletCreate {
+        name: String,
+        spec: WalletSpec,
+    },
+    WalletLoad(String),
+    WalletList,
+    WalletLabels(String),
+    WalletAddresses {
+        wallet: String,
+        label: String,
+    },
+    WalletBackup {
+        wallet: String,
+        out: PathBuf,
+        db: Option<String>,
+    },
+    WalletRestore {
+        file: PathBuf,
+        name: Option<String>,
+        rescan_from: Option<u64>,
+        db: Option<String>,
+    },
+    WalletVerify {
+        wallet: String,
+        file: PathBuf,
+    },
+    WalletEncrypt(String),
+    Mine {
+        blocks: u64,
+        to: Option<String>,
+    },
+    MineUntilSpendable {
+        to: Option<String>,
+    },
+    Send {
+        from: Option<String>,
+        to: Option<String>,
+        amount: Amount,
+        options: SendOptions,
+    },
+    SendMany {
+        from: Option<String>,
+        payment: Payment,
+    },
+    BumpFee {
+        txid: Txid,
+        from: Option<String>,
+        fee_rate: f64,
+        // Return a PSBT instead of signing and broadcasting
+        psbt: bool,
+        out: Option<PathBuf>,
+    },
+    Cpfp {
+        parent: Txid,
+        wallet: Option<String>,
+        fee_rate: f64,
+    },
+    PsbtCreate {
+        from: Option<String>,
+        payment: Payment,
+        out: Option<PathBuf>,
+    },
+    PsbtInspect(PathBuf),
+    PsbtSign {
+        file: PathBuf,
+        wallet: Option<String>,
+        out: Option<PathBuf>,
+    },
+    PsbtFinalize {
+        file: PathBuf,
+        out: Option<PathBuf>,
+    },
+    PsbtBroadcast(PathBuf),
+    MultisigCreate {
+        name: String,
+        threshold: usize,
+        cosigners: Vec<String>,
+    },
+    MultisigSign {
+        file: PathBuf,
+        cosigners: Vec<String>,
+        out: Option<PathBuf>,
+    },
+    MempoolShow(Txid),
+    MempoolList,
+    Report {
+        txid: Txid,
+        from: Option<String>,
+    },
+    Scenario(PathBuf),
+    Help,
+}
+
+fn usage<T>(message: impl Into<String>) -> Result<T> {
+    Err(Error::Usage(message.into()))
+}
+
+/// Recipients and funding settings of `send-many` and `psbt create`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payment {
+    recipients: Vec<(Address<NetworkUnchecked>, Amount)>,
+    // Checked against the node's network once connected, like the recipients
+    change_address: Option<Address<NetworkUnchecked>>,
+    options: SendOptions,
+}
+
+impl Payment {
+    fn parse(command: &str, recipients: &[&str], args: &mut Args) -> Result<Payment> {
+        if recipients.is_empty() {
+            return usage(format!("{command} needs at least one <address>=<btc>"));
+        }
+        let recipients = recipients
+            .iter()
+            .map(|r| parse_recipient(r))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut options = args.send_options()?;
+        if args.switch("--include-watching") {
+            options = options.include_watching(true);
+        }
+        if let Some(position) = args.take("--change-position") {
+            match position.parse() {
+                Ok(position) => options = options.change_position(position),
+                Err(_) => return usage(format!("invalid change position {position:?}")),
+            }
+        }
+        if let Some(locktime) = args.take("--locktime") {
+            match locktime.parse() {
+                Ok(locktime) => options = options.locktime(locktime),
+                Err(_) => return usage(format!("invalid locktime {locktime:?}")),
+            }
+        }
+        let change_address = args
+            .take("--change-address")
+            .map(|a| parse_address(&a))
+            .transpose()?;
+
+        Ok(Payment {
+            recipients,
+            change_address,
+            options,
+        })
+    }
+
+    // Recipients and options with every address checked against the node's network.
+    fn checked(self, node: &Node) -> Result<(Vec<(Address, Amount)>, SendOptions)> {
+        let network = node.network();
+        let recipients = self
+            .recipients
+            .into_iter()
+            .map(|(address, amount)| Ok((address.require_network(network)?, amount)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut options = self.options;
+        if let Some(address) = self.change_address {
+            options = options.change_address(address.require_network(network)?);
+        }
+        Ok((recipients, options))
+    }
+}
+
+// Flags of `send-many` and `psbt create` besides their own.
+const FUNDING_FLAGS: [&str; 9] = [
+    "--fee-rate",
+    "--conf-target",
+    "--estimate-mode",
+    "--subtract-fee",
+    "--replaceable",
+    "--change-address",
+    "--change-position",
+    "--locktime",
+    "--include-watching",
+];
+
+// Flags that take no value.
+const SWITCHES: [&str; 9] = [
+    "--subtract-fee",
+    "--replaceable",
+    "--include-watching",
+    "--psbt",
+    "--watch-only",
+    "--blank",
+    "--avoid-reuse",
+    "--legacy",
+    "--load-on-startup",
+];
+
+// Positional arguments and `--flag value` options of a subcommand.
+// Switches are stored with an empty value.
+struct Args {
+    positional: Vec<String>,
+    flags: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: Vec<String>) -> Result<Args> {
+        let mut positional = Vec::new();
+        let mut flags = HashMap::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                positional.push("help".to_owned());
+                continue;
+            }
+            if !arg.starts_with("--") {
+                positional.push(arg);
+                continue;
+            }
+            let (flag, value) = if SWITCHES.contains(&arg.as_str()) {
+                (arg, String::new())
+            } else {
+                match arg.split_once('=') {
+                    Some((flag, value)) => (flag.to_owned(), value.to_owned()),
+                    None => match args.next() {
+                        Some(value) => (arg, value),
+                        None => return usage(format!("missing value for {arg}")),
+                    },
+                }
+            };
+            if flags.contains_key(&flag) {
+                return usage(format!("{flag} given more than once"));
+            }
+            flags.insert(flag, value);
+        }
+
+        Ok(Args { positional, flags })
+    }
+
+    // Fails on any flag the subcommand doesn't know.
+    fn flags(self, known: &[&str]) -> Result<Args> {
+        if let Some(flag) = self.flags.keys().find(|f| !known.contains(&f.as_str())) {
+            return usage(format!("unknown flag {flag}"));
+        }
+        Ok(self)
+    }
+
+    fn take(&mut self, flag: &str) -> Option<String> {
+        self.flags.remove(flag)
+    }
+
+    fn switch(&mut self, flag: &str) -> bool {
+        self.flags.remove(flag).is_some()
+    }
+
+    // Comma-separated `--cosigners` wallet names.
+    fn cosigners(&mut self, command: &str) -> Result<Vec<String>> {
+        match self.take("--cosigners") {
+            Some(list) => Ok(list
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect()),
+            None => usage(format!("{command} needs --cosigners <wallet>,<wallet>,...")),
+        }
+    }
+
+    // `--fee-rate` of commands that need one.
+    fn required_fee_rate(&mut self, command: &str) -> Result<f64> {
+        match self.take("--fee-rate") {
+            Some(rate) => parse_fee_rate(&rate),
+            None => usage(format!("{command} needs --fee-rate <sat/vB>")),
+        }
+    }
+
+    // `--fee-rate`, `--conf-target`, `--estimate-mode` and `--subtract-fee`.
+    fn send_options(&mut self) -> Result<SendOptions> {
+        let mut options = SendOptions::new().subtract_fee(self.switch("--subtract-fee"));
+        if self.switch("--replaceable") {
+            options = options.replaceable(true);
+        }
+        if let Some(rate) = self.take("--fee-rate") {
+            options = options.fee_rate(parse_fee_rate(&rate)?);
+        }
+        if let Some(target) = self.take("--conf-target") {
+            match target.parse() {
+                Ok(target) => options = options.conf_target(target),
+                Err(_) => return usage(format!("invalid confirmation target {target:?}")),
+            }
+        }
+        if let Some(mode) = self.take("--estimate-mode") {
+            options = options.estimate_mode(mode.parse().map_err(Error::Usage)?);
+        }
+        Ok(options)
+    }
+}
+
+impl Command {
+    /// Parses the arguments left over after config flags were taken out.
+    pub fn parse(args: Vec<String>) -> Result<Command> {
+        let mut args = Args::parse(args)?;
+        let positional = std::mem::take(&mut args.positional);
+        let words: Vec<&str> = positional.iter().map(String::as_str).collect();
+
+        let command = match words.as_slice() {
+            [] | ["run"] => {
+                args.flags(&[])?;
+                Command::Run
+            }
+            ["help"] => Command::Help,
+            ["wallet", "create", name] => {
+                let name = name.to_string();
+                let mut args = args.flags(&[
+                    "--watch-only",
+                    "--blank",
+                    "--avoid-reuse",
+                    "--legacy",
+                    "--load-on-startup",
+                ])?;
+                let mut spec = WalletSpec::new()
+                    .disable_private_keys(args.switch("--watch-only"))
+                    .blank(args.switch("--blank"))
+                    .descriptors(!args.switch("--legacy"))
+                    .avoid_reuse(args.switch("--avoid-reuse"));
+                if args.switch("--load-on-startup") {
+                    spec = spec.load_on_startup(true);
+                }
+                Command::WalletCreate { name, spec }
+            }
+            ["wallet", "load", name] => {
+                let name = name.to_string();
+                args.flags(&[])?;
+                Command::WalletLoad(name)
+            }
+            ["wallet", "list"] => {
+                args.flags(&[])?;
+                Command::WalletList
+            }
+            ["wallet", "labels", name] => {
+                let name = name.to_string();
+                args.flags(&[])?;
+                Command::WalletLabels(name)
+            }
+            ["wallet", "addresses", name] => {
+                let wallet = name.to_string();
+                let mut args = args.flags(&["--label"])?;
+                match args.take("--label") {
+                    Some(label) => Command::WalletAddresses { wallet, label },
+                    None => return usage("wallet addresses needs --label"),
+                }
+            }
+            ["wallet", "backup", name] => {
+                let wallet = name.to_string();
+                let mut args = args.flags(&["--out", "--db"])?;
+                match args.take("--out") {
+                    Some(out) => Command::WalletBackup {
+                        wallet,
+                        out: PathBuf::from(out),
+                        db: args.take("--db"),
+                    },
+                    None => return usage("wallet backup needs --out <file>"),
+                }
+            }
+            ["wallet", "restore", file] => {
+                let file = PathBuf::from(file);
+                let mut args = args.flags(&["--as", "--rescan-from", "--db"])?;
+                let rescan_from = match args.take("--rescan-from") {
+                    Some(time) => match time.parse() {
+                        Ok(time) => Some(time),
+                        Err(_) => return usage(format!("invalid rescan time {time:?}")),
+                    },
+                    None => None,
+                };
+                let db = args.take("--db");
+                if db.is_some() && rescan_from.is_some() {
+                    return usage("--rescan-from doesn't apply to a --db restore");
+                }
+                Command::WalletRestore {
+                    file,
+                    name: args.take("--as"),
+                    rescan_from,
+                    db,
+                }
+            }
+            ["wallet", "verify", name, file] => {
+                let wallet = name.to_string();
+                let file = PathBuf::from(file);
+                args.flags(&[])?;
+                Command::WalletVerify { wallet, file }
+            }
+            ["wallet", "encrypt", name] => {
+                let name = name.to_string();
+                args.flags(&[])?;
+                Command::WalletEncrypt(name)
+            }
+            ["mine", "until-spendable"] => {
+                let mut args = args.flags(&["--to"])?;
+                Command::MineUntilSpendable {
+                    to: args.take("--to"),
+                }
+            }
+            ["mine", blocks] => {
+                let blocks = match blocks.parse() {
+                    Ok(blocks) => blocks,
+                    Err(_) => return usage(format!("invalid block count {blocks:?}")),
+                };
+                let mut args = args.flags(&["--to"])?;
+                Command::Mine {
+                    blocks,
+                    to: args.take("--to"),
+                }
+            }
+            ["send"] => {
+                let mut args = args.flags(&[
+                    "--from",
+                    "--to",
+                    "--amount",
+                    "--fee-rate",
+                    "--conf-target",
+                    "--estimate-mode",
+                    "--subtract-fee",
+                    "--replaceable",
+                ])?;
+                let amount = match args.take("--amount") {
+                    Some(amount) => parse_btc(&amount)?,
+                    None => return usage("send needs --amount"),
+                };
+                Command::Send {
+                    from: args.take("--from"),
+                    to: args.take("--to"),
+                    amount,
+                    options: args.send_options()?,
+                }
+            }
+            ["send-many", recipients @ ..] => {
+                let mut args = args.flags(&[&["--from"][..], &FUNDING_FLAGS].concat())?;
+                Command::SendMany {
+                    payment: Payment::parse("send-many", recipients, &mut args)?,
+                    from: args.take("--from"),
+                }
+            }
+            ["bump-fee", txid] => {
+                let txid = parse_txid(txid)?;
+                let mut args = args.flags(&["--from", "--fee-rate", "--psbt", "--out"])?;
+                let fee_rate = args.required_fee_rate("bump-fee")?;
+                let psbt = args.switch("--psbt");
+                let out = args.take("--out").map(PathBuf::from);
+                if out.is_some() && !psbt {
+                    return usage("--out only applies to bump-fee --psbt");
+                }
+                Command::BumpFee {
+                    txid,
+                    from: args.take("--from"),
+                    fee_rate,
+                    psbt,
+                    out,
+                }
+            }
+            ["cpfp", parent] => {
+                let parent = parse_txid(parent)?;
+                let mut args = args.flags(&["--wallet", "--fee-rate"])?;
+                Command::Cpfp {
+                    parent,
+                    fee_rate: args.required_fee_rate("cpfp")?,
+                    wallet: args.take("--wallet"),
+                }
+            }
+            ["psbt", "create", recipients @ ..] => {
+                let mut args = args.flags(&[&["--from", "--out"][..], &FUNDING_FLAGS].concat())?;
+                Command::PsbtCreate {
+                    payment: Payment::parse("psbt create", recipients, &mut args)?,
+                    from: args.take("--from"),
+                    out: args.take("--out").map(PathBuf::from),
+                }
+            }
+            ["psbt", "inspect", file] => {
+                let file = PathBuf::from(file);
+                args.flags(&[])?;
+                Command::PsbtInspect(file)
+            }
+            ["psbt", "sign", file] => {
+                let file = PathBuf::from(file);
+                let mut args = args.flags(&["--wallet", "--out"])?;
+                Command::PsbtSign {
+                    file,
+                    wallet: args.take("--wallet"),
+                    out: args.take("--out").map(PathBuf::from),
+                }
+            }
+            ["psbt", "finalize", file] => {
+                let file = PathBuf::from(file);
+                let mut args = args.flags(&["--out"])?;
+                Command::PsbtFinalize {
+                    file,
+                    out: args.take("--out").map(PathBuf::from),
+                }
+            }
+            ["psbt", "broadcast", file] => {
+                let file = PathBuf::from(file);
+                args.flags(&[])?;
+                Command::PsbtBroadcast(file)
+            }
+            ["multisig", "create", name] => {
+                let name = name.to_string();
+                let mut args = args.flags(&["--threshold", "--cosigners"])?;
+                let threshold = match args.take("--threshold") {
+                    Some(k) => match k.parse() {
+                        Ok(k) => k,
+                        Err(_) => return usage(format!("invalid threshold {k:?}")),
+                    },
+                    None => return usage("multisig create needs --threshold <k>"),
+                };
+                Command::MultisigCreate {
+                    name,
+                    threshold,
+                    cosigners: args.cosigners("multisig create")?,
+                }
+            }
+            ["multisig", "sign", file] => {
+                let file = PathBuf::from(file);
+                let mut args = args.flags(&["--cosigners", "--out"])?;
+                Command::MultisigSign {
+                    file,
+                    cosigners: args.cosigners("multisig sign")?,
+                    out: args.take("--out").map(PathBuf::from),
+                }
+            }
+            ["mempool", "show", txid] => {
+                let txid = parse_txid(txid)?;
+                args.flags(&[])?;
+                Command::MempoolShow(txid)
+            }
+            ["mempool", "list"] => {
+                args.flags(&[])?;
+                Command::MempoolList
+            }
+            ["report", txid] => {
+                let txid = parse_txid(txid)?;
+                let mut args = args.flags(&["--from"])?;
+                Command::Report {
+                    txid,
+                    from: args.take("--from"),
+                }
+            }
+            ["scenario", path] => {
+                let path = PathBuf::from(path);
+                args.flags(&[])?;
+                Command::Scenario(path)
+            }
+            _ => return usage(format!("unknown command {:?}", words.join(" "))),
+        };
+
+        Ok(command)
+    }
+
+    /// Runs the command against `node`, printing results to stdout.
+    pub fn execute(self, node: &Node) -> Result<()> {
+        let config = node.config();
+        let miner = || config.miner_wallet.clone();
+        let trader = || config.trader_wallet.clone();
+
+        match self {
+            Command::Run => {
+                // Run the Miner -> Trader payment flow
+                let report = workflow::run(node)?;
+
+                // Write the data to the configured output file (../out.txt by default), in the
+                // ten-line format given in readme.md unless another format is configured
+                report.write(config.format, File::create(&config.output)?)?;
+            }
+            Command::WalletCreate { name, spec } => {
+                let (_, outcome) = node.reconcile(&name, Some(&spec))?;
+                println!("Wallet {name}: {}", outcome.matched(&name)?);
+            }
+            Command::WalletLoad(name) => {
+                let (_, outcome) = node.load_wallet(&name)?;
+                println!("Wallet {name}: {outcome}");
+            }
+            Command::WalletList => {
+                for (name, loaded) in node.list_wallets()? {
+                    let state = if loaded { "loaded" } else { "not loaded" };
+                    println!("{name}\t{state}");
+                }
+            }
+            Command::WalletLabels(name) => {
+                let (wallet, _) = node.load_wallet(&name)?;
+                for label in wallet.labels()? {
+                    println!("{label:?}");
+                }
+            }
+            Command::WalletAddresses { wallet, label } => {
+                let (wallet, _) = node.load_wallet(&wallet)?;
+                for address in wallet.addresses_by_label(&label)? {
+                    println!("{address}");
+                }
+            }
+            Command::WalletBackup { wallet, out, db } => {
+                let (wallet, _) = node.load_wallet(&wallet)?;
+                // Private descriptors are only listed while the wallet is unlocked
+                let unlocked = node.unlock(&wallet)?;
+                let backup = backup::backup(&wallet)?;
+                drop(unlocked);
+                backup.save(&out)?;
+                println!(
+                    "Backed up {} descriptor(s), {} address(es) of {} to {}",
+                    backup.descriptors.len(),
+                    backup.labels.len(),
+                    wallet.name(),
+                    out.display()
+                );
+                if let Some(path) = db {
+                    backup::backup_file(&wallet, &path)?;
+                    println!("Node copied the wallet database to {path}");
+                }
+            }
+            Command::WalletRestore {
+                file,
+                name,
+                rescan_from,
+                db,
+            } => {
+                let backup = Backup::load(&file)?;
+                let name = name.unwrap_or_else(|| backup.wallet.clone());
+                let (wallet, verification) = match db {
+                    Some(path) => backup::restore_file(node, &backup, &name, &path)?,
+                    None => backup::restore(node, &backup, &name, rescan_from)?,
+                };
+                println!("Restored {}: {verification}", wallet.name());
+            }
+            Command::WalletVerify { wallet, file } => {
+                let backup = Backup::load(&file)?;
+                let (wallet, _) = node.load_wallet(&wallet)?;
+                let verification = backup::verify(&wallet, &backup)?;
+                println!("{}: {verification}", wallet.name());
+                for address in &verification.missing {
+                    println!("missing: {address}");
+                }
+                for address in &verification.extra {
+                    println!("extra: {address}");
+                }
+                if !verification.matches() {
+                    return Err(backup::BackupError::Mismatch {
+                        wallet: wallet.name().to_owned(),
+                        verification,
+                    }
+                    .into());
+                }
+            }
+            Command::WalletEncrypt(name) => {
+                let wallet = node.ensure_wallet(&name)?;
+                if wallet.is_encrypted()? {
+                    println!("Wallet {name} is already encrypted");
+                } else {
+                    wallet.encrypt(&*node.passphrase(&name)?)?;
+                    println!("Wallet {name} is encrypted and locked");
+                }
+            }
+            Command::Mine { blocks, to } => {
+                let wallet = node.ensure_wallet(&to.unwrap_or_else(miner))?;
+                let address = wallet.new_labeled_address(MINING_REWARD_LABEL)?;
+                let hashes = wallet.mine(blocks, &address)?;
+                println!("Mined {} block(s) to {address}", hashes.len());
+                if let Some(tip) = hashes.last() {
+                    println!("Tip: {tip}");
+                }
+            }
+            Command::MineUntilSpendable { to } => {
+                let wallet = node.ensure_wallet(&to.unwrap_or_else(miner))?;
+                let address = wallet.new_labeled_address(MINING_REWARD_LABEL)?;
+                let maturity = mining::mine_until_spendable(&wallet, &address, |progress| {
+                    println!(
+                        "Block {}: trusted {}, immature {}",
+                        progress.blocks_mined, progress.trusted, progress.immature
+                    );
+                })?;
+                println!("{}", maturity.explanation());
+                println!("{} balance: {}", wallet.name(), maturity.trusted);
+            }
+            Command::Send {
+                from,
+                to,
+                amount,
+                options,
+            } => {
+                let sender = node.ensure_wallet(&from.unwrap_or_else(miner))?;
+                let receiver = node.ensure_wallet(&to.unwrap_or_else(trader))?;
+                let address = receiver.new_labeled_address(RECEIVED_LABEL)?;
+                let _unlocked = node.unlock(&sender)?;
+                let sent = sender.send_to_address(&address, amount, &options)?;
+                println!("Sent {amount} to {address}");
+                println!(
+                    "Fee: {} ({} vB, {:.2} sat/vB)",
+                    sent.fee,
+                    sent.vsize,
+                    sent.fee_rate()
+                );
+                println!("{}", sent.txid);
+            }
+            Command::SendMany { from, payment } => {
+                let sender = node.ensure_wallet(&from.unwrap_or_else(miner))?;
+                let (recipients, options) = payment.checked(node)?;
+                let _unlocked = node.unlock(&sender)?;
+                match sender.send(&recipients, &options)? {
+                    SendResult::Complete(txid) => {
+                        let sent = sender.sent(txid)?;
+                        println!(
+                            "Fee: {} ({} vB, {:.2} sat/vB)",
+                            sent.fee,
+                            sent.vsize,
+                            sent.fee_rate()
+                        );
+                        println!("{txid}");
+                    }
+                    SendResult::Incomplete(psbt) => {
+                        println!("Not fully signed, nothing was broadcast. PSBT:");
+                        println!("{psbt}");
+                    }
+                }
+            }
+            Command::BumpFee {
+                txid,
+                from,
+                fee_rate,
+                psbt,
+                out,
+            } => {
+                let (sender, _) = node.load_wallet(&from.unwrap_or_else(miner))?;
+                if psbt {
+                    let bumped = bump::psbt_bump_fee(&sender, &txid, fee_rate)?;
+                    println!(
+                        "Replacement fee {} (was {}); sign and broadcast this PSBT to replace {txid}:",
+                        bumped.fee, bumped.original_fee
+                    );
+                    emit(&bumped.psbt, out.as_deref())?;
+                } else {
+                    let _unlocked = node.unlock(&sender)?;
+                    let bumped = bump::bump_fee(&sender, &txid, fee_rate)?;
+                    bumped.write(config.format, std::io::stdout().lock())?;
+                }
+            }
+            Command::Cpfp {
+                parent,
+                wallet,
+                fee_rate,
+            } => {
+                let (receiver, _) = node.load_wallet(&wallet.unwrap_or_else(trader))?;
+                let _unlocked = node.unlock(&receiver)?;
+                println!("{}", cpfp::accelerate(&receiver, &parent, fee_rate)?);
+            }
+            Command::PsbtCreate { from, payment, out } => {
+                let sender = node.ensure_wallet(&from.unwrap_or_else(miner))?;
+                let (recipients, options) = payment.checked(node)?;
+                let funded = psbt::create(&sender, &recipients, &options)?;
+                println!("Funded by {}, fee {}", sender.name(), funded.fee);
+                emit(&funded.psbt, out.as_deref())?;
+            }
+            Command::PsbtInspect(file) => {
+                let summary = psbt::inspect(node.rpc(), &psbt::load(&file)?)?;
+                println!("{summary}");
+            }
+            Command::PsbtSign { file, wallet, out } => {
+                let (signer, _) = node.load_wallet(&wallet.unwrap_or_else(miner))?;
+                let _unlocked = node.unlock(&signer)?;
+                let processed = psbt::sign(&signer, &psbt::load(&file)?)?;
+                let state = if processed.complete {
+                    "all inputs signed"
+                } else {
+                    "more signatures needed"
+                };
+                println!("Signed by {}: {state}", signer.name());
+                emit(&processed.psbt, out.as_deref())?;
+            }
+            Command::PsbtFinalize { file, out } => {
+                match psbt::finalize(node.rpc(), &psbt::load(&file)?)? {
+                    psbt::Finalized::Complete(hex) => {
+                        println!("Finalized; transaction hex:");
+                        emit(&hex, out.as_deref())?;
+                    }
+                    psbt::Finalized::Incomplete(psbt) => {
+                        println!("Not every input is signed yet; PSBT:");
+                        emit(&psbt, out.as_deref())?;
+                    }
+                }
+            }
+            Command::PsbtBroadcast(file) => {
+                let txid = psbt::broadcast(node.rpc(), &psbt::load(&file)?)?;
+                println!("{txid}");
+            }
+            Command::MultisigCreate {
+                name,
+                threshold,
+                cosigners,
+            } => {
+                let cosigners = cosigners
+                    .iter()
+                    .map(|name| node.ensure_wallet(name))
+                    .collect::<Result<Vec<_>>>()?;
+                let created = multisig::create(node, &name, threshold, &cosigners)?;
+                println!(
+                    "Created {name}: {threshold} of {} ({})",
+                    created.cosigners.len(),
+                    created.cosigners.join(", ")
+                );
+                println!("Receive descriptor: {}", created.receive_descriptor);
+                println!("Change descriptor: {}", created.change_descriptor);
+                println!("First address: {}", created.coordinator.new_address()?);
+            }
+            Command::MultisigSign {
+                file,
+                cosigners,
+                out,
+            } => {
+                let cosigners = cosigners
+                    .iter()
+                    .map(|name| Ok(node.load_wallet(name)?.0))
+                    .collect::<Result<Vec<_>>>()?;
+                let _unlocked = cosigners
+                    .iter()
+                    .map(|cosigner| node.unlock(cosigner))
+                    .collect::<Result<Vec<_>>>()?;
+                let signed = multisig::collect_signatures(&cosigners, &psbt::load(&file)?)?;
+                for round in &signed.rounds {
+                    let state = if round.complete {
+                        "complete"
+                    } else {
+                        "more signatures needed"
+                    };
+                    println!("Signed by {}: {state}", round.signer);
+                }
+                emit(&signed.psbt, out.as_deref())?;
+            }
+            Command::MempoolShow(txid) => {
+                println!("{}", mempool::entry(node.rpc(), &txid)?);
+                for (title, related) in [
+                    ("Ancestors", mempool::ancestors(node.rpc(), &txid)?),
+                    ("Descendants", mempool::descendants(node.rpc(), &txid)?),
+                ] {
+                    println!("{title}:");
+                    for (txid, entry) in related {
+                        println!(
+                            "  {txid}  {} vB  {}  {:.2} sat/vB",
+                            entry.vsize,
+                            entry.fees.base,
+                            entry.fee_rate()
+                        );
+                    }
+                }
+            }
+            Command::MempoolList => {
+                for (txid, entry) in mempool::all(node.rpc())? {
+                    println!("{txid}\n{entry}\n");
+                }
+            }
+            Command::Report { txid, from } => {
+                let (sender, _) = node.load_wallet(&from.unwrap_or_else(miner))?;
+                let report = TransactionReport::extract(node, &sender, &txid)?;
+                report.write(config.format, std::io::stdout().lock())?;
+            }
+            Command::Scenario(path) => {
+                let report = Scenario::from_file(&path)?.run(node);
+                println!("{report}");
+                if !report.passed() {
+                    return Err(Error::ScenarioFailed(path.display().to_string()));
+                }
+            }
+            Command::Help => println!("{USAGE}"),
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_btc(value: &str) -> Result<Amount> {
+    match Amount::from_str_in(value, Denomination::Bitcoin) {
+        Ok(amount) => Ok(amount),
+        Err(e) => usage(format!("invalid amount {value:?}: {e}")),
+    }
+}
+
+// A fee rate in sat/vB; `f64` also parses "NaN", "inf" and negatives, which no
+// transaction can pay.
+fn parse_fee_rate(value: &str) -> Result<f64> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => usage(format!("invalid fee rate {value:?}")),
+    }
+}
+
+// Writes `data` to `out`, or prints it when no file is given.
+fn emit(data: &str, out: Option<&Path>) -> Result<()> {
+    match out {
+        Some(path) => {
+            psbt::save(path, data)?;
+            println!("Saved to {}", path.display());
+        }
+        None => println!("{data}"),
+    }
+    Ok(())
+}
+
+fn parse_address(value: &str) -> Result<Address<NetworkUnchecked>> {
+    match value.parse() {
+        Ok(address) => Ok(address),
+        Err(e) => usage(format!("invalid address {value:?}: {e}")),
+    }
+}
+
+// `<address>=<btc>`
+fn parse_recipient(value: &str) -> Result<(Address<NetworkUnchecked>, Amount)> {
+    match value.split_once('=') {
+        Some((address, amount)) => Ok((parse_address(address)?, parse_btc(amount)?)),
+        None => usage(format!("expected <address>=<btc>, got {value:?}")),
+    }
+}
+
+fn parse_txid(value: &str) -> Result<Txid> {
+    match Txid::from_str(value) {
+        Ok(txid) => Ok(txid),
+        Err(_) => usage(format!("invalid txid {value:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TXID: &str = "6c2fae5e0e51422beb17fde022dca96279827a9f20852e8179f6d6dadaef9505";
+    const TRADER_ADDRESS: &str = "bcrt1qqyqszqgpqyqszqgpqyqszqgpqyqszqgpvxat9t";
+    const CHANGE_ADDRESS: &str = "bcrt1qqgpqyqszqgpqyqszqgpqyqszqgpqyqszazmwwa";
+
+    fn parse(line: &str) -> Result<Command> {
+        Command::parse(line.split_whitespace().map(str::to_owned).collect())
+    }
+
+    fn usage_error(line: &str) -> String {
+        match parse(line) {
+            Err(Error::Usage(message)) => message,
+            other => panic!("expected a usage error for {line:?}, got {other:?}"),
+        }
+    }
+
+    fn txid() -> Txid {
+        Txid::from_str(TXID).unwrap()
+    }
+
+    fn path(path: &str) -> PathBuf {
+        PathBuf::from(path)
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn payment(options: SendOptions) -> Payment {
+        Payment {
+            recipients: vec![(
+                TRADER_ADDRESS.parse().unwrap(),
+                Amount::from_btc(1.5).unwrap(),
+            )],
+            change_address: None,
+            options,
+        }
+    }
+
+    #[test]
+    fn parses_each_subcommand() {
+        let cases = [
+            ("", Command::Run),
+            ("run", Command::Run),
+            ("help", Command::Help),
+            ("--help", Command::Help),
+            (
+                "wallet create Vault --watch-only --blank --avoid-reuse --load-on-startup",
+                Command::WalletCreate {
+                    name: "Vault".to_owned(),
+                    spec: WalletSpec::watch_only()
+                        .blank(true)
+                        .avoid_reuse(true)
+                        .load_on_startup(true),
+                },
+            ),
+            (
+                "wallet create Old --legacy",
+                Command::WalletCreate {
+                    name: "Old".to_owned(),
+                    spec: WalletSpec::new().descriptors(false),
+                },
+            ),
+            ("wallet load Miner", Command::WalletLoad("Miner".to_owned())),
+            ("wallet list", Command::WalletList),
+            (
+                "wallet labels Miner",
+                Command::WalletLabels("Miner".to_owned()),
+            ),
+            (
+                "wallet addresses Trader --label Received",
+                Command::WalletAddresses {
+                    wallet: "Trader".to_owned(),
+                    label: "Received".to_owned(),
+                },
+            ),
+            (
+                "wallet backup Miner --out miner.json --db /backups/miner.dat",
+                Command::WalletBackup {
+                    wallet: "Miner".to_owned(),
+                    out: path("miner.json"),
+                    db: Some("/backups/miner.dat".to_owned()),
+                },
+            ),
+            (
+                "wallet restore miner.json --as Restored --rescan-from 1700000000",
+                Command::WalletRestore {
+                    file: path("miner.json"),
+                    name: Some("Restored".to_owned()),
+                    rescan_from: Some(1700000000),
+                    db: None,
+                },
+            ),
+            (
+                "wallet verify Miner miner.json",
+                Command::WalletVerify {
+                    wallet: "Miner".to_owned(),
+                    file: path("miner.json"),
+                },
+            ),
+            (
+                "wallet encrypt Miner",
+                Command::WalletEncrypt("Miner".to_owned()),
+            ),
+            (
+                "mine 101 --to Trader",
+                Command::Mine {
+                    blocks: 101,
+                    to: Some("Trader".to_owned()),
+                },
+            ),
+            (
+                "mine until-spendable",
+                Command::MineUntilSpendable { to: None },
+            ),
+            (
+                "send --amount 20 --fee-rate 2.5 --replaceable",
+                Command::Send {
+                    from: None,
+                    to: None,
+                    amount: Amount::from_btc(20.0).unwrap(),
+                    options: SendOptions::new().fee_rate(2.5).replaceable(true),
+                },
+            ),
+            (
+                &format!("send-many --from Trader {TRADER_ADDRESS}=1.5 --subtract-fee"),
+                Command::SendMany {
+                    from: Some("Trader".to_owned()),
+                    payment: payment(SendOptions::new().subtract_fee(true)),
+                },
+            ),
+            (
+                &format!("bump-fee {TXID} --fee-rate 10 --psbt --out bump.psbt"),
+                Command::BumpFee {
+                    txid: txid(),
+                    from: None,
+                    fee_rate: 10.0,
+                    psbt: true,
+                    out: Some(path("bump.psbt")),
+                },
+            ),
+            (
+                &format!("cpfp {TXID} --fee-rate 20 --wallet Trader"),
+                Command::Cpfp {
+                    parent: txid(),
+                    wallet: Some("Trader".to_owned()),
+                    fee_rate: 20.0,
+                },
+            ),
+            (
+                &format!("psbt create {TRADER_ADDRESS}=1.5 --out pay.psbt --locktime 200"),
+                Command::PsbtCreate {
+                    from: None,
+                    payment: payment(SendOptions::new().locktime(200)),
+                    out: Some(path("pay.psbt")),
+                },
+            ),
+            (
+                "psbt inspect pay.psbt",
+                Command::PsbtInspect(path("pay.psbt")),
+            ),
+            (
+                "psbt sign pay.psbt --wallet Trader",
+                Command::PsbtSign {
+                    file: path("pay.psbt"),
+                    wallet: Some("Trader".to_owned()),
+                    out: None,
+                },
+            ),
+            (
+                "psbt finalize pay.psbt --out pay.hex",
+                Command::PsbtFinalize {
+                    file: path("pay.psbt"),
+                    out: Some(path("pay.hex")),
+                },
+            ),
+            (
+                "psbt broadcast pay.hex",
+                Command::PsbtBroadcast(path("pay.hex")),
+            ),
+            (
+                "multisig create Vault --threshold 2 --cosigners Miner,Trader,Cosigner",
+                Command::MultisigCreate {
+                    name: "Vault".to_owned(),
+                    threshold: 2,
+                    cosigners: names(&["Miner", "Trader", "Cosigner"]),
+                },
+            ),
+            (
+                "multisig sign spend.psbt --cosigners Miner,Trader",
+                Command::MultisigSign {
+                    file: path("spend.psbt"),
+                    cosigners: names(&["Miner", "Trader"]),
+                    out: None,
+                },
+            ),
+            (
+                &format!("mempool show {TXID}"),
+                Command::MempoolShow(txid()),
+            ),
+            ("mempool list", Command::MempoolList),
+            (
+                &format!("report {TXID} --from Trader"),
+                Command::Report {
+                    txid: txid(),
+                    from: Some("Trader".to_owned()),
+                },
+            ),
+            ("scenario flow.toml", Command::Scenario(path("flow.toml"))),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse(line).unwrap(), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn flags_take_their_value_after_an_equals_sign() {
+        assert_eq!(
+            parse(&format!(
+                "send-many {TRADER_ADDRESS}=1.5 --change-address={CHANGE_ADDRESS} --conf-target=6"
+            ))
+            .unwrap(),
+            Command::SendMany {
+                from: None,
+                payment: Payment {
+                    change_address: Some(CHANGE_ADDRESS.parse().unwrap()),
+                    ..payment(SendOptions::new().conf_target(6))
+                },
+            }
+        );
+        assert_eq!(
+            parse("wallet addresses Trader --label=a=b").unwrap(),
+            Command::WalletAddresses {
+                wallet: "Trader".to_owned(),
+                label: "a=b".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(
+            usage_error("wallet addresses Trader --label"),
+            "missing value for --label"
+        );
+        assert_eq!(usage_error("send"), "send needs --amount");
+        assert_eq!(
+            usage_error(&format!("cpfp {TXID}")),
+            "cpfp needs --fee-rate <sat/vB>"
+        );
+        assert_eq!(
+            usage_error("multisig create Vault --cosigners Miner,Trader"),
+            "multisig create needs --threshold <k>"
+        );
+        assert_eq!(
+            usage_error("send-many"),
+            "send-many needs at least one <address>=<btc>"
+        );
+        assert_eq!(usage_error("mine ten"), "invalid block count \"ten\"");
+    }
+
+    #[test]
+    fn rejects_fee_rates_no_transaction_can_pay() {
+        for rate in ["NaN", "inf", "-inf", "0", "-1", "0.0", "2sat"] {
+            let expected = format!("invalid fee rate {rate:?}");
+            assert_eq!(
+                usage_error(&format!("send --amount 1 --fee-rate {rate}")),
+                expected
+            );
+            assert_eq!(
+                usage_error(&format!("bump-fee {TXID} --fee-rate {rate}")),
+                expected
+            );
+            assert_eq!(
+                usage_error(&format!("cpfp {TXID} --fee-rate={rate}")),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_flags_the_subcommand_does_not_take() {
+        assert_eq!(usage_error("wallet list --to Miner"), "unknown flag --to");
+        assert_eq!(
+            usage_error(&format!("cpfp {TXID} --fee-rate 20 --psbt")),
+            "unknown flag --psbt"
+        );
+        assert_eq!(
+            usage_error("send --amount 1 --locktime 200"),
+            "unknown flag --locktime"
+        );
+        assert_eq!(
+            usage_error("wallet frobnicate"),
+            "unknown command \"wallet frobnicate\""
+        );
+    }
+
+    #[test]
+    fn rejects_repeated_flags() {
+        assert_eq!(
+            usage_error("send --amount 1 --amount=2"),
+            "--amount given more than once"
+        );
+        assert_eq!(
+            usage_error("wallet create Vault --blank --blank"),
+            "--blank given more than once"
+        );
+    }
+}
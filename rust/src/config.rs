@@ -0,0 +1,508 @@
+use crate::network::Chain;
+use crate::passphrase::PassphraseSource;
+use crate::report::ReportFormat;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs};
+
+// Default node access params, used when no other source provides a value.
+const DEFAULT_RPC_HOST: &str = "127.0.0.1";
+const DEFAULT_MINER_WALLET: &str = "Miner";
+const DEFAULT_TRADER_WALLET: &str = "Trader";
+const DEFAULT_OUTPUT: &str = "../out.txt";
+// Seconds an encrypted wallet stays unlocked for a signing operation.
+const DEFAULT_UNLOCK_TIMEOUT: u64 = 60;
+
+// Config file picked up from the working directory when none is given explicitly.
+const DEFAULT_CONFIG_FILE: &str = "capstone.toml";
+
+// Flags consumed by `Config::load`. Each takes a value.
+const CONFIG_FLAGS: [&str; 14] = [
+    "--config",
+    "--rpc-host",
+    "--rpc-port",
+    "--rpc-user",
+    "--rpc-pass",
+    "--rpc-cookie",
+    "--datadir",
+    "--network",
+    "--miner-wallet",
+    "--trader-wallet",
+    "--output",
+    "--format",
+    "--passphrase-from",
+    "--unlock-timeout",
+];
+
+// Prefix shared by every environment variable we read.
+const ENV_PREFIX: &str = "CAPSTONE_";
+
+/// Fully resolved settings the tool runs with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub rpc_host: String,
+    pub rpc_port: u16,
+    pub rpc_user: Option<String>,
+    pub rpc_pass: Option<String>,
+    pub rpc_cookie: Option<PathBuf>,
+    pub datadir: Option<PathBuf>,
+    /// Chain we expect the node to run. When unset, it is detected from the node.
+    pub network: Option<Chain>,
+    pub miner_wallet: String,
+    pub trader_wallet: String,
+    pub output: PathBuf,
+    pub format: ReportFormat,
+    /// Where to read the passphrase of encrypted wallets. When set, new wallets
+    /// are created encrypted with it.
+    pub passphrase_from: Option<PassphraseSource>,
+    /// Seconds `walletpassphrase` unlocks a wallet for; it is locked again as
+    /// soon as the operation finishes.
+    pub unlock_timeout: u64,
+}
+
+/// One layer of settings. Every field is optional so layers can be merged,
+/// later layers overriding earlier ones.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Layer {
+    rpc_host: Option<String>,
+    rpc_port: Option<u16>,
+    rpc_user: Option<String>,
+    rpc_pass: Option<String>,
+    rpc_cookie: Option<PathBuf>,
+    datadir: Option<PathBuf>,
+    network: Option<String>,
+    miner_wallet: Option<String>,
+    trader_wallet: Option<String>,
+    output: Option<PathBuf>,
+    format: Option<String>,
+    passphrase_from: Option<String>,
+    unlock_timeout: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Toml(PathBuf, Box<toml::de::Error>),
+    InvalidValue { key: &'static str, value: String },
+    MissingValue(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "cannot read config {}: {e}", path.display()),
+            ConfigError::Toml(path, e) => write!(f, "invalid config {}: {e}", path.display()),
+            ConfigError::InvalidValue { key, value } => {
+                write!(f, "invalid value for {key}: {value:?}")
+            }
+            ConfigError::MissingValue(flag) => write!(f, "missing value for {flag}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Resolves the configuration from all sources. Precedence, lowest to highest:
+    /// built-in defaults, TOML file, `CAPSTONE_*` environment variables, CLI flags.
+    ///
+    /// The file is taken from `--config`, then `CAPSTONE_CONFIG`, then
+    /// `./capstone.toml` if it exists.
+    ///
+    /// Config flags may appear anywhere in `args`; all other arguments are
+    /// returned untouched for the caller to parse.
+    pub fn load<I: IntoIterator<Item = String>>(
+        args: I,
+    ) -> Result<(Config, Vec<String>), ConfigError> {
+        let (cli, config_path, rest) = Layer::from_args(args)?;
+        let config_path = config_path
+            .or_else(|| env::var_os(format!("{ENV_PREFIX}CONFIG")).map(PathBuf::from))
+            .or_else(|| {
+                let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+                default.exists().then_some(default)
+            });
+
+        let file = match config_path {
+            Some(path) => Layer::from_file(&path)?,
+            None => Layer::default(),
+        };
+        let env = Layer::from_env(|key| env::var(format!("{ENV_PREFIX}{key}")).ok())?;
+
+        let config = file.merge(env).merge(cli).resolve()?;
+        Ok((config, rest))
+    }
+
+    pub fn rpc_url(&self) -> String {
+        format!("http://{}:{}", self.rpc_host, self.rpc_port)
+    }
+
+    pub fn wallet_url(&self, wallet_name: &str) -> String {
+        format!("{}/wallet/{wallet_name}", self.rpc_url())
+    }
+}
+
+impl Layer {
+    fn from_file(path: &Path) -> Result<Layer, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        toml::from_str(&text).map_err(|e| ConfigError::Toml(path.to_owned(), Box::new(e)))
+    }
+
+    fn from_env<F: Fn(&str) -> Option<String>>(var: F) -> Result<Layer, ConfigError> {
+        Ok(Layer {
+            rpc_host: var("RPC_HOST"),
+            rpc_port: var("RPC_PORT")
+                .map(|v| parse_port("CAPSTONE_RPC_PORT", &v))
+                .transpose()?,
+            rpc_user: var("RPC_USER"),
+            rpc_pass: var("RPC_PASS"),
+            rpc_cookie: var("RPC_COOKIE").map(PathBuf::from),
+            datadir: var("DATADIR").map(PathBuf::from),
+            network: var("NETWORK"),
+            miner_wallet: var("MINER_WALLET"),
+            trader_wallet: var("TRADER_WALLET"),
+            output: var("OUTPUT").map(PathBuf::from),
+            format: var("FORMAT"),
+            passphrase_from: var("PASSPHRASE_FROM"),
+            unlock_timeout: var("UNLOCK_TIMEOUT")
+                .map(|v| parse_timeout("CAPSTONE_UNLOCK_TIMEOUT", &v))
+                .transpose()?,
+        })
+    }
+
+    // Picks `--flag value` and `--flag=value` pairs for config flags out of `args`.
+    // Returns the CLI layer, the `--config` path, if one was given, and every
+    // argument that isn't a config flag, in order.
+    fn from_args<I: IntoIterator<Item = String>>(
+        args: I,
+    ) -> Result<(Layer, Option<PathBuf>, Vec<String>), ConfigError> {
+        let mut layer = Layer::default();
+        let mut config_path = None;
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+                None => (arg.clone(), None),
+            };
+            if !CONFIG_FLAGS.contains(&flag.as_str()) {
+                rest.push(arg);
+                continue;
+            }
+            let value = match inline.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(ConfigError::MissingValue(flag)),
+            };
+
+            match flag.as_str() {
+                "--config" => config_path = Some(PathBuf::from(value)),
+                "--rpc-host" => layer.rpc_host = Some(value),
+                "--rpc-port" => layer.rpc_port = Some(parse_port("--rpc-port", &value)?),
+                "--rpc-user" => layer.rpc_user = Some(value),
+                "--rpc-pass" => layer.rpc_pass = Some(value),
+                "--rpc-cookie" => layer.rpc_cookie = Some(PathBuf::from(value)),
+                "--datadir" => layer.datadir = Some(PathBuf::from(value)),
+                "--network" => layer.network = Some(value),
+                "--miner-wallet" => layer.miner_wallet = Some(value),
+                "--trader-wallet" => layer.trader_wallet = Some(value),
+                "--output" => layer.output = Some(PathBuf::from(value)),
+                "--format" => layer.format = Some(value),
+                "--passphrase-from" => layer.passphrase_from = Some(value),
+                "--unlock-timeout" => {
+                    layer.unlock_timeout = Some(parse_timeout("--unlock-timeout", &value)?)
+                }
+                _ => unreachable!("{flag} is listed in CONFIG_FLAGS"),
+            }
+        }
+
+        Ok((layer, config_path, rest))
+    }
+
+    fn merge(self, over: Layer) -> Layer {
+        Layer {
+            rpc_host: over.rpc_host.or(self.rpc_host),
+            rpc_port: over.rpc_port.or(self.rpc_port),
+            rpc_user: over.rpc_user.or(self.rpc_user),
+            rpc_pass: over.rpc_pass.or(self.rpc_pass),
+            rpc_cookie: over.rpc_cookie.or(self.rpc_cookie),
+            datadir: over.datadir.or(self.datadir),
+            network: over.network.or(self.network),
+            miner_wallet: over.miner_wallet.or(self.miner_wallet),
+            trader_wallet: over.trader_wallet.or(self.trader_wallet),
+            output: over.output.or(self.output),
+            format: over.format.or(self.format),
+            passphrase_from: over.passphrase_from.or(self.passphrase_from),
+            unlock_timeout: over.unlock_timeout.or(self.unlock_timeout),
+        }
+    }
+
+    fn resolve(self) -> Result<Config, ConfigError> {
+        let network = self
+            .network
+            .map(|value| {
+                value
+                    .parse::<Chain>()
+                    .map_err(|_| ConfigError::InvalidValue {
+                        key: "network",
+                        value,
+                    })
+            })
+            .transpose()?;
+        let format = self
+            .format
+            .map(|value| {
+                value
+                    .parse::<ReportFormat>()
+                    .map_err(|_| ConfigError::InvalidValue {
+                        key: "format",
+                        value,
+                    })
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let passphrase_from = self
+            .passphrase_from
+            .map(|value| {
+                value
+                    .parse::<PassphraseSource>()
+                    .map_err(|_| ConfigError::InvalidValue {
+                        key: "passphrase_from",
+                        value,
+                    })
+            })
+            .transpose()?;
+        if self.unlock_timeout == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                key: "unlock_timeout",
+                value: "0".to_owned(),
+            });
+        }
+
+        Ok(Config {
+            rpc_host: self.rpc_host.unwrap_or_else(|| DEFAULT_RPC_HOST.to_owned()),
+            // Without an explicit port, use the configured chain's default, else regtest's.
+            rpc_port: self
+                .rpc_port
+                .unwrap_or_else(|| network.unwrap_or(Chain::Regtest).default_rpc_port()),
+            rpc_user: self.rpc_user,
+            rpc_pass: self.rpc_pass,
+            rpc_cookie: self.rpc_cookie,
+            datadir: self.datadir,
+            network,
+            miner_wallet: self
+                .miner_wallet
+                .unwrap_or_else(|| DEFAULT_MINER_WALLET.to_owned()),
+            trader_wallet: self
+                .trader_wallet
+                .unwrap_or_else(|| DEFAULT_TRADER_WALLET.to_owned()),
+            output: self.output.unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT)),
+            format,
+            passphrase_from,
+            unlock_timeout: self.unlock_timeout.unwrap_or(DEFAULT_UNLOCK_TIMEOUT),
+        })
+    }
+}
+
+fn parse_port(key: &'static str, value: &str) -> Result<u16, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        key,
+        value: value.to_owned(),
+    })
+}
+
+fn parse_timeout(key: &'static str, value: &str) -> Result<u64, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        key,
+        value: value.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::process;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_owned).collect()
+    }
+
+    fn env_layer(vars: &[(&str, &str)]) -> Result<Layer, ConfigError> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        Layer::from_env(|key| vars.get(key).map(|v| v.to_string()))
+    }
+
+    fn cli_layer(line: &str) -> Layer {
+        Layer::from_args(args(line)).unwrap().0
+    }
+
+    fn file_layer(toml: &str) -> Layer {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn invalid_value(result: Result<impl fmt::Debug, ConfigError>) -> (&'static str, String) {
+        match result {
+            Err(ConfigError::InvalidValue { key, value }) => (key, value),
+            other => panic!("expected an invalid value, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn defaults_apply_when_nothing_is_set() {
+        let config = Layer::default().resolve().unwrap();
+
+        assert_eq!(config.rpc_host, "127.0.0.1");
+        assert_eq!(config.rpc_port, 18443);
+        assert_eq!(config.network, None);
+        assert_eq!(config.miner_wallet, "Miner");
+        assert_eq!(config.trader_wallet, "Trader");
+        assert_eq!(config.output, PathBuf::from("../out.txt"));
+        assert_eq!(config.format, ReportFormat::Text);
+        assert_eq!(config.passphrase_from, None);
+        assert_eq!(config.unlock_timeout, 60);
+    }
+
+    #[test]
+    fn env_overrides_file_and_cli_overrides_env() {
+        let file = file_layer(
+            r#"
+rpc_host = "file.example"
+rpc_port = 1111
+miner_wallet = "FileMiner"
+trader_wallet = "FileTrader"
+"#,
+        );
+        let env = env_layer(&[
+            ("RPC_PORT", "2222"),
+            ("MINER_WALLET", "EnvMiner"),
+            ("TRADER_WALLET", "EnvTrader"),
+        ])
+        .unwrap();
+        let cli = cli_layer("--trader-wallet CliTrader");
+
+        let config = file.merge(env).merge(cli).resolve().unwrap();
+
+        assert_eq!(config.rpc_host, "file.example");
+        assert_eq!(config.rpc_port, 2222);
+        assert_eq!(config.miner_wallet, "EnvMiner");
+        assert_eq!(config.trader_wallet, "CliTrader");
+    }
+
+    #[test]
+    fn port_defaults_to_the_configured_network() {
+        let config = cli_layer("--network signet").resolve().unwrap();
+        assert_eq!(config.network, Some(Chain::Signet));
+        assert_eq!(config.rpc_port, 38332);
+
+        let config = cli_layer("--network testnet4 --rpc-port 9999")
+            .resolve()
+            .unwrap();
+        assert_eq!(config.rpc_port, 9999);
+    }
+
+    #[test]
+    fn flags_take_inline_or_separate_values_and_leave_the_rest() {
+        let (inline, config_path, rest) = Layer::from_args(args(
+            "--rpc-port=1234 send --config=capstone.toml --format json --fee-rate=2 --rpc-user=a=b",
+        ))
+        .unwrap();
+        let (separate, _, _) = Layer::from_args(args("--rpc-port 1234")).unwrap();
+
+        assert_eq!(inline.rpc_port, Some(1234));
+        assert_eq!(separate.rpc_port, inline.rpc_port);
+        assert_eq!(inline.format.as_deref(), Some("json"));
+        // Only the first `=` separates the flag from its value
+        assert_eq!(inline.rpc_user.as_deref(), Some("a=b"));
+        assert_eq!(config_path, Some(PathBuf::from("capstone.toml")));
+        assert_eq!(rest, ["send", "--fee-rate=2"]);
+    }
+
+    #[test]
+    fn flag_without_a_value_is_rejected() {
+        match Layer::from_args(args("send --network")) {
+            Err(ConfigError::MissingValue(flag)) => assert_eq!(flag, "--network"),
+            other => panic!("expected a missing value, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_ports_are_rejected_in_every_layer() {
+        assert_eq!(
+            invalid_value(Layer::from_args(args("--rpc-port=70000"))),
+            ("--rpc-port", "70000".to_owned())
+        );
+        assert_eq!(
+            invalid_value(env_layer(&[("RPC_PORT", "port")])),
+            ("CAPSTONE_RPC_PORT", "port".to_owned())
+        );
+        assert!(toml::from_str::<Layer>("rpc_port = -1").is_err());
+    }
+
+    #[test]
+    fn invalid_network_is_rejected() {
+        for layer in [
+            cli_layer("--network moonnet"),
+            env_layer(&[("NETWORK", "moonnet")]).unwrap(),
+            file_layer(r#"network = "moonnet""#),
+        ] {
+            assert_eq!(
+                invalid_value(layer.resolve()),
+                ("network", "moonnet".to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_format_source_and_timeout_are_rejected() {
+        assert_eq!(
+            invalid_value(cli_layer("--format=xml").resolve()).0,
+            "format"
+        );
+        assert_eq!(
+            invalid_value(cli_layer("--passphrase-from=stdin").resolve()).0,
+            "passphrase_from"
+        );
+        assert_eq!(
+            invalid_value(cli_layer("--unlock-timeout=0").resolve()).0,
+            "unlock_timeout"
+        );
+    }
+
+    #[test]
+    fn file_rejects_unknown_keys() {
+        assert!(toml::from_str::<Layer>("rpc_hots = \"localhost\"").is_err());
+    }
+
+    #[test]
+    fn load_reads_the_config_file_under_the_flags() {
+        let path = env::temp_dir().join(format!("capstone-config-{}.toml", process::id()));
+        fs::write(
+            &path,
+            "rpc_host = \"file.example\"\nnetwork = \"regtest\"\nformat = \"csv\"\n",
+        )
+        .unwrap();
+
+        let loaded = Config::load(args(&format!(
+            "report --config {} --format=markdown",
+            path.display()
+        )));
+        fs::remove_file(&path).unwrap();
+
+        let (config, rest) = loaded.unwrap();
+        assert_eq!(config.rpc_host, "file.example");
+        assert_eq!(config.network, Some(Chain::Regtest));
+        assert_eq!(config.format, ReportFormat::Markdown);
+        assert_eq!(rest, ["report"]);
+    }
+
+    #[test]
+    fn load_reports_a_missing_config_file() {
+        let path = env::temp_dir().join(format!("capstone-config-missing-{}.toml", process::id()));
+
+        match Config::load(args(&format!("--config {}", path.display()))) {
+            Err(ConfigError::Io(missing, _)) => assert_eq!(missing, path),
+            other => panic!("expected an I/O error, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::error::Result;
+use crate::mempool;
+use crate::wallet::WalletHandle;
+use bitcoincore_rpc::bitcoin::absolute::LockTime;
+use bitcoincore_rpc::bitcoin::transaction::Version;
+use bitcoincore_rpc::bitcoin::{
+    Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use bitcoincore_rpc::RpcApi;
+use std::fmt;
+
+/// Why a child transaction could not be built or didn't reach its target.
+#[derive(Debug)]
+pub enum CpfpError {
+    /// The wallet has no unconfirmed output of the parent to spend.
+    NothingToSpend { wallet: String, parent: Txid },
+    /// The wallet couldn't sign every input of the child, e.g. because it is locked.
+    Unsigned {
+        wallet: String,
+        parent: Txid,
+        reason: String,
+    },
+    /// The wallet's outputs of the parent don't cover the fee the child needs.
+    Unaffordable {
+        parent: Txid,
+        available: Amount,
+        fee: Amount,
+    },
+    /// What the child would keep after its fee is below the dust limit, so
+    /// the node would refuse to relay it.
+    Dust {
+        parent: Txid,
+        value: Amount,
+        dust_limit: Amount,
+    },
+    /// The package fee rate is below the target: of the signed child before
+    /// broadcast, or in the node's mempool after it (`broadcast`), in which case
+    /// the child is already on its way to miners.
+    BelowTarget {
+        child: Txid,
+        package_fee_rate: f64,
+        target: f64,
+        broadcast: bool,
+    },
+}
+
+impl fmt::Display for CpfpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpfpError::NothingToSpend { wallet, parent } => {
+                write!(f, "wallet {wallet} has no unconfirmed output of {parent}")
+            }
+            CpfpError::Unsigned {
+                wallet,
+                parent,
+                reason,
+            } => write!(
+                f,
+                "wallet {wallet} could not sign a child of {parent}: {reason}"
+            ),
+            CpfpError::Unaffordable {
+                parent,
+                available,
+                fee,
+            } => write!(
+                f,
+                "outputs of {parent} hold {available}, not enough for a child fee of {fee}"
+            ),
+            CpfpError::Dust {
+                parent,
+                value,
+                dust_limit,
+            } => write!(
+                f,
+                "a child of {parent} would keep {value} after its fee, below the dust limit of {dust_limit}"
+            ),
+            CpfpError::BelowTarget {
+                child,
+                package_fee_rate,
+                target,
+                broadcast,
+            } => {
+                write!(
+                    f,
+                    "package of {child} pays {package_fee_rate:.2} sat/vB, below the target of {target:.2} sat/vB"
+                )?;
+                if *broadcast {
+                    write!(f, "; the child was already broadcast")
+                } else {
+                    write!(f, "; nothing was broadcast")
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpfpError {}
+
+/// A broadcast child and the package it forms with its unconfirmed ancestors,
+/// as the node's mempool reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cpfp {
+    pub parent: Txid,
+    pub child: Txid,
+    pub fee: Amount,
+    /// Virtual size of the child in vbytes.
+    pub vsize: u64,
+    /// Fees of the child and all its in-mempool ancestors.
+    pub package_fee: Amount,
+    pub package_vsize: u64,
+}
+
+impl Cpfp {
+    /// Fee rate of the whole package in sat/vB.
+    pub fn package_fee_rate(&self) -> f64 {
+        self.package_fee.to_sat() as f64 / self.package_vsize as f64
+    }
+}
+
+impl fmt::Display for Cpfp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (fee {}, {} vB); package of {} tx: {} over {} vB, {:.2} sat/vB",
+            self.child,
+            self.fee,
+            self.vsize,
+            self.parent,
+            self.package_fee,
+            self.package_vsize,
+            self.package_fee_rate()
+        )
+    }
+}
+
+/// Speeds up `parent`, an unconfirmed payment to `wallet`, by spending the
+/// wallet's outputs of it back to itself with a fee high enough that the child,
+/// the parent and the parent's unconfirmed ancestors together pay `fee_rate`
+/// sat/vB. Miners select the package by that rate.
+///
+/// The fee comes from the parent's `getmempoolentry` (ancestor fees and size)
+/// and the signed child's vsize. The signed package is checked to reach
+/// `fee_rate` before broadcast, and the child's own mempool entry after it.
+pub fn accelerate(wallet: &WalletHandle, parent: &Txid, fee_rate: f64) -> Result<Cpfp> {
+    let rpc = wallet.client();
+    let parent_entry = mempool::entry(rpc, parent)?;
+
+    // Outputs received from another wallet aren't trusted until confirmed,
+    // so they're only listed with include_unsafe
+    let outputs: Vec<_> = rpc
+        .list_unspent(Some(0), Some(0), None, Some(true), None)?
+        .into_iter()
+        .filter(|utxo| utxo.txid == *parent)
+        .collect();
+    if outputs.is_empty() {
+        return Err(CpfpError::NothingToSpend {
+            wallet: wallet.name().to_owned(),
+            parent: *parent,
+        }
+        .into());
+    }
+    let available = outputs.iter().map(|utxo| utxo.amount).sum::<Amount>();
+
+    let mut child = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: outputs
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: OutPoint::new(utxo.txid, utxo.vout),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: available,
+            script_pubkey: wallet.new_address()?.script_pubkey(),
+        }],
+    };
+
+    // Sign once to learn the child's size; the fee doesn't change it
+    let vsize = sign(wallet, parent, &child)?.vsize() as u64;
+    let package_target = fee_rate * (parent_entry.ancestor_size + vsize) as f64;
+    let needed = package_target.ceil() as u64;
+    let paid = parent_entry.fees.ancestor.to_sat();
+    // If the ancestors already pay enough, the child still pays the rate for itself
+    let fee = Amount::from_sat(
+        needed
+            .saturating_sub(paid)
+            .max((fee_rate * vsize as f64).ceil() as u64),
+    );
+    if fee >= available {
+        return Err(CpfpError::Unaffordable {
+            parent: *parent,
+            available,
+            fee,
+        }
+        .into());
+    }
+    let dust_limit = child.output[0].script_pubkey.dust_value();
+    if available - fee < dust_limit {
+        return Err(CpfpError::Dust {
+            parent: *parent,
+            value: available - fee,
+            dust_limit,
+        }
+        .into());
+    }
+    child.output[0].value = available - fee;
+
+    let signed = sign(wallet, parent, &child)?;
+    let package_fee = parent_entry.fees.ancestor + fee;
+    let package_vsize = parent_entry.ancestor_size + signed.vsize() as u64;
+    let package_fee_rate = package_fee.to_sat() as f64 / package_vsize as f64;
+    if package_fee_rate < fee_rate {
+        return Err(CpfpError::BelowTarget {
+            child: signed.txid(),
+            package_fee_rate,
+            target: fee_rate,
+            broadcast: false,
+        }
+        .into());
+    }
+    let txid = rpc.send_raw_transaction(&signed)?;
+
+    let entry = mempool::entry(rpc, &txid)?;
+    let cpfp = Cpfp {
+        parent: *parent,
+        child: txid,
+        fee: entry.fees.base,
+        vsize: entry.vsize,
+        package_fee: entry.fees.ancestor,
+        package_vsize: entry.ancestor_size,
+    };
+    if cpfp.package_fee_rate() < fee_rate {
+        return Err(CpfpError::BelowTarget {
+            child: txid,
+            package_fee_rate: cpfp.package_fee_rate(),
+            target: fee_rate,
+            broadcast: true,
+        }
+        .into());
+    }
+    Ok(cpfp)
+}
+
+// Signs `child` with `wallet`. Only a fully signed child has its final size.
+fn sign(wallet: &WalletHandle, parent: &Txid, child: &Transaction) -> Result<Transaction> {
+    let signed = wallet
+        .client()
+        .sign_raw_transaction_with_wallet(child, None, None)?;
+    if !signed.complete {
+        let reason = signed
+            .errors
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| e.error)
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(CpfpError::Unsigned {
+            wallet: wallet.name().to_owned(),
+            parent: *parent,
+            reason: if reason.is_empty() {
+                "signing is incomplete".to_owned()
+            } else {
+                reason
+            },
+        }
+        .into());
+    }
+    Ok(signed.transaction()?)
+}
@@ -0,0 +1,259 @@
+use crate::auth::{AuthMethod, SkippedCookie};
+use crate::backup::BackupError;
+use crate::config::ConfigError;
+use crate::cpfp::CpfpError;
+use crate::multisig::MultisigError;
+use crate::network::NetworkError;
+use crate::passphrase::PassphraseError;
+use crate::scenario::ScenarioError;
+use crate::wallet::{SpecMismatch, WalletError};
+use bitcoincore_rpc::bitcoin::{address, amount, consensus, Txid};
+use bitcoincore_rpc::jsonrpc;
+use std::{fmt, io};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Every way a run can fail. Each variant maps to a process exit code, see [`Error::exit_code`].
+#[derive(Debug)]
+pub enum Error {
+    /// Bad command line.
+    Usage(String),
+    Config(ConfigError),
+    Scenario(ScenarioError),
+    /// The node answered HTTP 401 to these credentials, which were tried
+    /// after skipping an unreadable cookie if `skipped_cookie` is set.
+    Unauthorized {
+        method: AuthMethod,
+        skipped_cookie: Option<SkippedCookie>,
+    },
+    Network(NetworkError),
+    Rpc(bitcoincore_rpc::Error),
+    Address(address::Error),
+    Amount(amount::ParseAmountError),
+    Io(io::Error),
+    /// A transaction failed to decode from its network serialization.
+    Decode(consensus::encode::Error),
+    /// A report or other output failed to serialize as JSON.
+    Json(serde_json::Error),
+    WalletNotFound(String),
+    Wallet(WalletError),
+    /// An existing wallet isn't set up as the requested [`WalletSpec`](crate::wallet::WalletSpec).
+    WalletMismatch {
+        wallet: String,
+        mismatches: Vec<SpecMismatch>,
+    },
+    /// The transaction has no block hash yet, so it cannot be reported on.
+    TxUnconfirmed(Txid),
+    /// An input spends an output its parent transaction doesn't have.
+    MissingPrevout {
+        txid: Txid,
+        vout: u32,
+    },
+    /// Mining this many blocks still left the wallet without a spendable balance.
+    NotSpendable {
+        wallet: String,
+        blocks: u64,
+    },
+    Cpfp(CpfpError),
+    Multisig(MultisigError),
+    Backup(BackupError),
+    Passphrase(PassphraseError),
+    /// A scenario step's check didn't hold.
+    Assertion(String),
+    ScenarioFailed(String),
+}
+
+impl Error {
+    /// Process exit code: 2 usage/config, 3 cannot reach or authenticate to the node,
+    /// 4 wrong network, 5 the node returned an error, 6 transaction/wallet state,
+    /// 7 local I/O, decoding or encoding, 8 scenario failures.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Usage(_) | Error::Config(_) | Error::Scenario(_) | Error::Passphrase(_) => 2,
+            Error::Multisig(MultisigError::InvalidThreshold { .. })
+            | Error::Multisig(MultisigError::DuplicateCosigner(_))
+            | Error::Backup(BackupError::Parse(_))
+            | Error::Backup(BackupError::Unsupported { .. }) => 2,
+            Error::Unauthorized { .. } => 3,
+            Error::Rpc(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_))) => 3,
+            Error::Network(NetworkError::Rpc(_)) => 3,
+            Error::Network(_) => 4,
+            Error::Rpc(_) => 5,
+            Error::Address(_)
+            | Error::Amount(_)
+            | Error::WalletNotFound(_)
+            | Error::Wallet(_)
+            | Error::WalletMismatch { .. }
+            | Error::TxUnconfirmed(_)
+            | Error::MissingPrevout { .. }
+            | Error::NotSpendable { .. }
+            | Error::Cpfp(_)
+            | Error::Multisig(_)
+            | Error::Backup(_) => 6,
+            Error::Io(_) | Error::Decode(_) | Error::Json(_) => 7,
+            Error::Assertion(_) | Error::ScenarioFailed(_) => 8,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(message) => f.write_str(message),
+            Error::Config(e) => e.fmt(f),
+            Error::Scenario(e) => e.fmt(f),
+            Error::Unauthorized {
+                method,
+                skipped_cookie,
+            } => {
+                write!(f, "node rejected credentials (HTTP 401) using {method}")?;
+                match skipped_cookie {
+                    Some(skipped) => write!(f, " ({skipped})"),
+                    None => Ok(()),
+                }
+            }
+            Error::Network(e) => e.fmt(f),
+            Error::Rpc(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e))) => {
+                write!(f, "node returned error {}: {}", e.code, e.message)
+            }
+            Error::Rpc(e) => write!(f, "RPC failed: {e}"),
+            Error::Address(e) => write!(f, "bad address: {e}"),
+            Error::Amount(e) => write!(f, "bad amount: {e}"),
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Decode(e) => write!(f, "cannot decode transaction: {e}"),
+            Error::Json(e) => write!(f, "cannot write JSON: {e}"),
+            Error::WalletNotFound(name) => write!(f, "no wallet named {name:?} on the node"),
+            Error::Wallet(e) => e.fmt(f),
+            Error::WalletMismatch { wallet, mismatches } => {
+                let mismatches: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+                write!(
+                    f,
+                    "wallet {wallet} doesn't match the requested setup: {}",
+                    mismatches.join(", ")
+                )
+            }
+            Error::TxUnconfirmed(txid) => write!(f, "transaction {txid} is not confirmed yet"),
+            Error::MissingPrevout { txid, vout } => {
+                write!(f, "previous output {txid}:{vout} does not exist")
+            }
+            Error::NotSpendable { wallet, blocks } => {
+                write!(
+                    f,
+                    "wallet {wallet} has no spendable balance after mining {blocks} block(s)"
+                )
+            }
+            Error::Cpfp(e) => e.fmt(f),
+            Error::Multisig(e) => e.fmt(f),
+            Error::Backup(e) => e.fmt(f),
+            Error::Passphrase(e) => e.fmt(f),
+            Error::Assertion(message) => f.write_str(message),
+            Error::ScenarioFailed(name) => write!(f, "scenario {name} failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(e) => Some(e),
+            Error::Scenario(e) => Some(e),
+            Error::Network(e) => Some(e),
+            Error::Rpc(e) => Some(e),
+            Error::Address(e) => Some(e),
+            Error::Amount(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Wallet(e) => Some(e),
+            Error::Cpfp(e) => Some(e),
+            Error::Multisig(e) => Some(e),
+            Error::Backup(e) => Some(e),
+            Error::Passphrase(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<bitcoincore_rpc::Error> for Error {
+    fn from(e: bitcoincore_rpc::Error) -> Error {
+        Error::Rpc(e)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Error {
+        Error::Config(e)
+    }
+}
+
+impl From<ScenarioError> for Error {
+    fn from(e: ScenarioError) -> Error {
+        Error::Scenario(e)
+    }
+}
+
+impl From<NetworkError> for Error {
+    fn from(e: NetworkError) -> Error {
+        Error::Network(e)
+    }
+}
+
+impl From<WalletError> for Error {
+    fn from(e: WalletError) -> Error {
+        Error::Wallet(e)
+    }
+}
+
+impl From<CpfpError> for Error {
+    fn from(e: CpfpError) -> Error {
+        Error::Cpfp(e)
+    }
+}
+
+impl From<MultisigError> for Error {
+    fn from(e: MultisigError) -> Error {
+        Error::Multisig(e)
+    }
+}
+
+impl From<BackupError> for Error {
+    fn from(e: BackupError) -> Error {
+        Error::Backup(e)
+    }
+}
+
+impl From<PassphraseError> for Error {
+    fn from(e: PassphraseError) -> Error {
+        Error::Passphrase(e)
+    }
+}
+
+impl From<address::Error> for Error {
+    fn from(e: address::Error) -> Error {
+        Error::Address(e)
+    }
+}
+
+impl From<amount::ParseAmountError> for Error {
+    fn from(e: amount::ParseAmountError) -> Error {
+        Error::Amount(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<consensus::encode::Error> for Error {
+    fn from(e: consensus::encode::Error) -> Error {
+        Error::Decode(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
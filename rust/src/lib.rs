@@ -0,0 +1,27 @@
+//! Tools for driving a Bitcoin Core node over RPC: connecting, managing the
+//! Miner/Trader wallets, sending payments and reporting on them.
+
+pub mod auth;
+pub mod backup;
+pub mod bump;
+pub mod config;
+pub mod cpfp;
+pub mod error;
+pub mod mempool;
+pub mod mining;
+pub mod multisig;
+pub mod network;
+pub mod node;
+pub mod passphrase;
+pub mod psbt;
+pub mod report;
+pub mod scenario;
+pub mod send;
+pub mod wallet;
+pub mod workflow;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use node::Node;
+pub use report::TransactionReport;
+pub use wallet::WalletHandle;
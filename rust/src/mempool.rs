@@ -0,0 +1,129 @@
+use crate::error::Result;
+use bitcoincore_rpc::bitcoin::{Amount, Txid, Wtxid};
+use bitcoincore_rpc::{Client, RpcApi};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A transaction's entry in the node's mempool, as returned by `getmempoolentry`
+/// and the verbose forms of `getrawmempool`, `getmempoolancestors` and
+/// `getmempooldescendants`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MempoolEntry {
+    /// Virtual size in vbytes (BIP 141).
+    pub vsize: u64,
+    #[serde(default)]
+    pub weight: Option<u64>,
+    /// When the transaction entered the pool, in seconds since the epoch.
+    pub time: u64,
+    /// Block height when the transaction entered the pool.
+    pub height: u64,
+    /// In-mempool ancestors, including this transaction.
+    #[serde(rename = "ancestorcount")]
+    pub ancestor_count: u64,
+    #[serde(rename = "ancestorsize")]
+    pub ancestor_size: u64,
+    /// In-mempool descendants, including this transaction.
+    #[serde(rename = "descendantcount")]
+    pub descendant_count: u64,
+    #[serde(rename = "descendantsize")]
+    pub descendant_size: u64,
+    pub wtxid: Wtxid,
+    pub fees: MempoolFees,
+    /// Unconfirmed transactions this one spends from.
+    pub depends: Vec<Txid>,
+    /// Unconfirmed transactions spending this one.
+    #[serde(rename = "spentby")]
+    pub spent_by: Vec<Txid>,
+    // Nodes running with full RBF may leave this out; treat it as not signalled.
+    #[serde(rename = "bip125-replaceable", default)]
+    pub bip125_replaceable: bool,
+    #[serde(default)]
+    pub unbroadcast: Option<bool>,
+}
+
+/// Fees of a mempool entry, in BTC on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MempoolFees {
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub base: Amount,
+    /// Base fee plus any `prioritisetransaction` delta.
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub modified: Amount,
+    /// Modified fees of all in-mempool ancestors, including this transaction.
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub ancestor: Amount,
+    /// Modified fees of all in-mempool descendants, including this transaction.
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub descendant: Amount,
+}
+
+impl MempoolEntry {
+    /// Base fee rate in sat/vB.
+    pub fn fee_rate(&self) -> f64 {
+        self.fees.base.to_sat() as f64 / self.vsize as f64
+    }
+
+    /// Fee rate of this transaction together with its unconfirmed ancestors, in sat/vB.
+    pub fn ancestor_fee_rate(&self) -> f64 {
+        self.fees.ancestor.to_sat() as f64 / self.ancestor_size as f64
+    }
+}
+
+impl fmt::Display for MempoolEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "wtxid: {}", self.wtxid)?;
+        writeln!(f, "vsize: {} vB", self.vsize)?;
+        writeln!(
+            f,
+            "fees: base {}, modified {}, ancestor {}, descendant {}",
+            self.fees.base, self.fees.modified, self.fees.ancestor, self.fees.descendant
+        )?;
+        writeln!(f, "fee rate: {:.2} sat/vB", self.fee_rate())?;
+        writeln!(
+            f,
+            "ancestors: {} ({} vB), descendants: {} ({} vB)",
+            self.ancestor_count, self.ancestor_size, self.descendant_count, self.descendant_size
+        )?;
+        writeln!(f, "depends: {}", join_txids(&self.depends))?;
+        writeln!(f, "spent by: {}", join_txids(&self.spent_by))?;
+        write!(f, "bip125-replaceable: {}", self.bip125_replaceable)
+    }
+}
+
+fn join_txids(txids: &[Txid]) -> String {
+    if txids.is_empty() {
+        return "-".to_owned();
+    }
+    txids
+        .iter()
+        .map(Txid::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `getmempoolentry`: fails with an RPC error if `txid` is not in the mempool.
+pub fn entry(rpc: &Client, txid: &Txid) -> Result<MempoolEntry> {
+    Ok(rpc.call("getmempoolentry", &[txid.to_string().into()])?)
+}
+
+/// `getmempoolancestors` (verbose): the unconfirmed transactions `txid` depends on.
+pub fn ancestors(rpc: &Client, txid: &Txid) -> Result<HashMap<Txid, MempoolEntry>> {
+    Ok(rpc.call(
+        "getmempoolancestors",
+        &[txid.to_string().into(), true.into()],
+    )?)
+}
+
+/// `getmempooldescendants` (verbose): the unconfirmed transactions spending from `txid`.
+pub fn descendants(rpc: &Client, txid: &Txid) -> Result<HashMap<Txid, MempoolEntry>> {
+    Ok(rpc.call(
+        "getmempooldescendants",
+        &[txid.to_string().into(), true.into()],
+    )?)
+}
+
+/// `getrawmempool` (verbose): every transaction in the mempool.
+pub fn all(rpc: &Client) -> Result<HashMap<Txid, MempoolEntry>> {
+    Ok(rpc.call("getrawmempool", &[true.into()])?)
+}
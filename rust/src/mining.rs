@@ -0,0 +1,70 @@
+use crate::error::{Error, Result};
+use crate::wallet::WalletHandle;
+use bitcoincore_rpc::bitcoin::{Address, Amount};
+use bitcoincore_rpc::RpcApi;
+
+/// Confirmations a coinbase output needs before it can be spent (consensus rule).
+pub const COINBASE_MATURITY: u64 = 100;
+
+// A fresh wallet needs one block for the reward plus COINBASE_MATURITY on top of it.
+// If that many blocks don't produce a spendable balance (e.g. the regtest subsidy
+// has halved to zero), mining more won't either.
+const MAX_BLOCKS: u64 = COINBASE_MATURITY + 1;
+
+/// Wallet balances after some number of mined blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Maturity {
+    pub blocks_mined: u64,
+    /// Spendable balance.
+    pub trusted: Amount,
+    /// Coinbase rewards with fewer than `COINBASE_MATURITY` confirmations.
+    pub immature: Amount,
+}
+
+impl Maturity {
+    /// Why it took `blocks_mined` blocks to get a spendable balance.
+    pub fn explanation(&self) -> String {
+        format!(
+            "Block rewards can only be spent after {COINBASE_MATURITY} confirmations \
+             (COINBASE_MATURITY), so each reward stays immature until {COINBASE_MATURITY} \
+             more blocks are mined on top of it. It took {} block(s) for the first reward \
+             to mature; {} is still immature.",
+            self.blocks_mined, self.immature
+        )
+    }
+}
+
+/// Mines one block at a time to `address` until `wallet` has a positive
+/// trusted balance, calling `on_progress` after every block.
+///
+/// Returns right away if the wallet can already spend something.
+pub fn mine_until_spendable<F: FnMut(&Maturity)>(
+    wallet: &WalletHandle,
+    address: &Address,
+    mut on_progress: F,
+) -> Result<Maturity> {
+    let mut progress = balances(wallet, 0)?;
+
+    while progress.trusted == Amount::ZERO {
+        if progress.blocks_mined == MAX_BLOCKS {
+            return Err(Error::NotSpendable {
+                wallet: wallet.name().to_owned(),
+                blocks: MAX_BLOCKS,
+            });
+        }
+        wallet.mine(1, address)?;
+        progress = balances(wallet, progress.blocks_mined + 1)?;
+        on_progress(&progress);
+    }
+
+    Ok(progress)
+}
+
+fn balances(wallet: &WalletHandle, blocks_mined: u64) -> Result<Maturity> {
+    let balances = wallet.client().get_balances()?;
+    Ok(Maturity {
+        blocks_mined,
+        trusted: balances.mine.trusted,
+        immature: balances.mine.immature,
+    })
+}
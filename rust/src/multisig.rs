@@ -0,0 +1,283 @@
+use crate::error::Result;
+use crate::node::Node;
+use crate::psbt;
+use crate::wallet::{Reconciled, WalletHandle, WalletSpec};
+use bitcoincore_rpc::bitcoin::bip32::{DerivationPath, Xpriv, Xpub};
+use bitcoincore_rpc::bitcoin::secp256k1::Secp256k1;
+use bitcoincore_rpc::bitcoin::Network;
+use bitcoincore_rpc::RpcApi;
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+
+// Limit of keys in a `sortedmulti` inside `wsh`, from Core's policy.
+const MAX_COSIGNERS: usize = 20;
+
+/// Why a multisig wallet could not be set up.
+#[derive(Debug)]
+pub enum MultisigError {
+    /// Not 1 <= threshold <= cosigners <= 20.
+    InvalidThreshold {
+        threshold: usize,
+        cosigners: usize,
+    },
+    DuplicateCosigner(String),
+    /// The wallet has no active wpkh receive descriptor with private keys to
+    /// derive a multisig key from, e.g. because it is watch-only.
+    NoCosignerKey(String),
+    /// The wallet's wpkh descriptor isn't derived from a master private key.
+    UnsupportedDescriptor(String),
+}
+
+impl fmt::Display for MultisigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultisigError::InvalidThreshold {
+                threshold,
+                cosigners,
+            } => write!(
+                f,
+                "cannot require {threshold} of {cosigners} signatures (at most {MAX_COSIGNERS} cosigners)"
+            ),
+            MultisigError::DuplicateCosigner(wallet) => {
+                write!(f, "cosigner {wallet} is listed more than once")
+            }
+            MultisigError::NoCosignerKey(wallet) => write!(
+                f,
+                "wallet {wallet} has no active wpkh descriptor with private keys to derive a multisig key from"
+            ),
+            MultisigError::UnsupportedDescriptor(wallet) => write!(
+                f,
+                "wallet {wallet}'s wpkh descriptor isn't derived from a master private key"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MultisigError {}
+
+/// A k-of-n watch-only wallet over the cosigners' keys. It tracks the multisig
+/// coins and creates PSBTs spending them; the cosigners sign.
+pub struct Multisig {
+    pub coordinator: WalletHandle,
+    pub threshold: usize,
+    pub cosigners: Vec<String>,
+    /// `wsh(sortedmulti(...))` for receive addresses, with checksum.
+    pub receive_descriptor: String,
+    /// The same over the cosigners' change keys.
+    pub change_descriptor: String,
+}
+
+/// One cosigner's turn at signing a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningRound {
+    pub signer: String,
+    /// Whether the PSBT had all signatures it needs after this round.
+    pub complete: bool,
+}
+
+/// The PSBT after the signing rounds, ready for [`psbt::finalize`] when complete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signed {
+    pub psbt: String,
+    pub rounds: Vec<SigningRound>,
+    pub complete: bool,
+}
+
+/// A cosigner's multisig key: the BIP 48 P2WSH account (`m/48h/<coin>h/0h/2h`)
+/// of its wallet's master key, so none of the keys behind its own single-key
+/// addresses end up in the multisig.
+pub struct CosignerKey {
+    /// The account xpub with its origin, `[fingerprint/48h/1h/0h/2h]tpub...`.
+    pub account: String,
+    // The master xprv followed by the account path, for the signing descriptors
+    signing: String,
+}
+
+// The master key of a wallet-generated `wpkh(tprv.../84h/1h/0h/0/*)#checksum`.
+fn master_key(desc: &str) -> Option<(&str, Xpriv)> {
+    let (desc, _checksum) = desc.split_once('#').unwrap_or((desc, ""));
+    let key = desc.strip_prefix("wpkh(")?.strip_suffix(')')?;
+    let (encoded, _path) = key.split_once('/')?;
+    let master = Xpriv::from_str(encoded).ok()?;
+    (master.depth == 0).then_some((encoded, master))
+}
+
+/// Derives `wallet`'s [`CosignerKey`] from the master key of its active wpkh
+/// receive descriptor in `listdescriptors true`, so the wallet must hold
+/// private keys and be unlocked.
+pub fn cosigner_key(wallet: &WalletHandle) -> Result<CosignerKey> {
+    let no_key = || MultisigError::NoCosignerKey(wallet.name().to_owned());
+    // Watch-only wallets have no master key to derive from
+    let descriptors = wallet.descriptors(true)?.ok_or_else(no_key)?;
+    let receive = descriptors
+        .iter()
+        .find(|d| d.active && !d.internal && d.desc.starts_with("wpkh("))
+        .ok_or_else(no_key)?;
+    let unsupported = || MultisigError::UnsupportedDescriptor(wallet.name().to_owned());
+    let (encoded, master) = master_key(&receive.desc).ok_or_else(unsupported)?;
+
+    let coin = if master.network == Network::Bitcoin {
+        0
+    } else {
+        1
+    };
+    let path = format!("48h/{coin}h/0h/2h");
+    let secp = Secp256k1::new();
+    let account = DerivationPath::from_str(&format!("m/{path}"))
+        .and_then(|path| master.derive_priv(&secp, &path))
+        .map_err(|_| unsupported())?;
+    Ok(CosignerKey {
+        account: format!(
+            "[{}/{path}]{}",
+            master.fingerprint(&secp),
+            Xpub::from_priv(&secp, &account)
+        ),
+        signing: format!("{encoded}/{path}"),
+    })
+}
+
+// `desc` with the checksum `getdescriptorinfo` computes for it.
+fn with_checksum(node: &Node, desc: &str) -> Result<String> {
+    let info = node.rpc().get_descriptor_info(desc)?;
+    Ok(match info.checksum {
+        Some(checksum) => format!("{desc}#{checksum}"),
+        None => info.descriptor,
+    })
+}
+
+// The descriptors `wallet` lists, without checksums.
+fn listed_descriptors(wallet: &WalletHandle, private: bool) -> Result<Vec<String>> {
+    Ok(wallet
+        .descriptors(private)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|d| match d.desc.split_once('#') {
+            Some((desc, _checksum)) => desc.to_owned(),
+            None => d.desc,
+        })
+        .collect())
+}
+
+// Gives `cosigner` the private keys of its multisig account in inactive
+// `pk(...)` descriptors. It then signs the multisig inputs of a PSBT through
+// the key paths the PSBT carries, while the multisig coins stay out of its
+// balance and it never hands out these keys' addresses.
+fn import_signing_keys(node: &Node, cosigner: &WalletHandle, key: &CosignerKey) -> Result<()> {
+    let present = listed_descriptors(cosigner, true)?;
+    let mut requests = Vec::new();
+    for branch in [0, 1] {
+        let desc = format!("pk({}/{branch}/*)", key.signing);
+        if !present.contains(&desc) {
+            requests.push(json!({
+                "desc": with_checksum(node, &desc)?,
+                "active": false,
+                "timestamp": "now",
+            }));
+        }
+    }
+    if requests.is_empty() {
+        return Ok(());
+    }
+    cosigner.import_descriptors(requests)
+}
+
+/// Creates `name`, a blank watch-only descriptor wallet, and imports
+/// `wsh(sortedmulti(threshold, ...))` receive and change descriptors built from
+/// each cosigner's [`CosignerKey`]. Each cosigner gets the private keys of its
+/// multisig account in descriptors of their own, so it can sign.
+///
+/// Running it again changes nothing: the wallets are reconciled, see
+/// [`crate::wallet::reconcile`], and descriptors they already have aren't imported again.
+pub fn create(
+    node: &Node,
+    name: &str,
+    threshold: usize,
+    cosigners: &[WalletHandle],
+) -> Result<Multisig> {
+    let n = cosigners.len();
+    if threshold == 0 || threshold > n || n > MAX_COSIGNERS {
+        return Err(MultisigError::InvalidThreshold {
+            threshold,
+            cosigners: n,
+        }
+        .into());
+    }
+    let mut names: Vec<String> = Vec::with_capacity(n);
+    for cosigner in cosigners {
+        if names.iter().any(|n| n == cosigner.name()) {
+            return Err(MultisigError::DuplicateCosigner(cosigner.name().to_owned()).into());
+        }
+        names.push(cosigner.name().to_owned());
+    }
+
+    let mut keys = Vec::with_capacity(n);
+    for cosigner in cosigners {
+        // Private descriptors are only listed and imported while the wallet is unlocked
+        let _unlocked = node.unlock(cosigner)?;
+        let key = cosigner_key(cosigner)?;
+        import_signing_keys(node, cosigner, &key)?;
+        keys.push(key.account);
+    }
+    let descriptor = |branch: u32| -> Result<String> {
+        let keys: Vec<String> = keys.iter().map(|k| format!("{k}/{branch}/*")).collect();
+        with_checksum(
+            node,
+            &format!("wsh(sortedmulti({threshold},{}))", keys.join(",")),
+        )
+    };
+    let receive_descriptor = descriptor(0)?;
+    let change_descriptor = descriptor(1)?;
+
+    let spec = WalletSpec::watch_only().blank(true);
+    let (coordinator, outcome) = node.reconcile(name, Some(&spec))?;
+    let present = match outcome.matched(name)? {
+        Reconciled::Created => Vec::new(),
+        _ => listed_descriptors(&coordinator, false)?,
+    };
+    let requests: Vec<Value> = [(&receive_descriptor, false), (&change_descriptor, true)]
+        .into_iter()
+        .filter(|(desc, _)| {
+            let (desc, _checksum) = desc.split_once('#').unwrap_or((desc, ""));
+            !present.iter().any(|p| p == desc)
+        })
+        .map(|(desc, internal)| {
+            json!({ "desc": desc, "active": true, "internal": internal, "timestamp": "now" })
+        })
+        .collect();
+    if !requests.is_empty() {
+        coordinator.import_descriptors(requests)?;
+    }
+
+    Ok(Multisig {
+        coordinator,
+        threshold,
+        cosigners: names,
+        receive_descriptor,
+        change_descriptor,
+    })
+}
+
+/// Passes `psbt` from cosigner to cosigner, each adding its signatures with
+/// [`psbt::sign`], and stops as soon as the PSBT is complete, so only as many
+/// cosigners as needed sign.
+pub fn collect_signatures(cosigners: &[WalletHandle], psbt: &str) -> Result<Signed> {
+    let mut signed = Signed {
+        psbt: psbt.to_owned(),
+        rounds: Vec::new(),
+        complete: false,
+    };
+    for cosigner in cosigners {
+        let processed = psbt::sign(cosigner, &signed.psbt)?;
+        signed.psbt = processed.psbt;
+        signed.complete = processed.complete;
+        signed.rounds.push(SigningRound {
+            signer: cosigner.name().to_owned(),
+            complete: processed.complete,
+        });
+        if signed.complete {
+            break;
+        }
+    }
+    Ok(signed)
+}
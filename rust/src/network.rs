@@ -0,0 +1,139 @@
+use bitcoincore_rpc::bitcoin::Network;
+use bitcoincore_rpc::{Client, RpcApi};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// The chain a node runs on, as reported by `getblockchaininfo`.
+///
+/// The RPC library's `Network` predates testnet4, so we keep our own enum and
+/// only convert to `Network` for address parsing and rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chain {
+    Main,
+    Testnet3,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+impl Chain {
+    pub const ALL: [Chain; 5] = [
+        Chain::Regtest,
+        Chain::Signet,
+        Chain::Testnet4,
+        Chain::Testnet3,
+        Chain::Main,
+    ];
+
+    /// Name used by Bitcoin Core in `getblockchaininfo.chain` and `-chain=`.
+    pub fn core_name(self) -> &'static str {
+        match self {
+            Chain::Main => "main",
+            Chain::Testnet3 => "test",
+            Chain::Testnet4 => "testnet4",
+            Chain::Signet => "signet",
+            Chain::Regtest => "regtest",
+        }
+    }
+
+    /// Subdirectory of the datadir bitcoind uses for this chain.
+    pub fn datadir_subdir(self) -> &'static str {
+        match self {
+            Chain::Main => "",
+            Chain::Testnet3 => "testnet3",
+            Chain::Testnet4 => "testnet4",
+            Chain::Signet => "signet",
+            Chain::Regtest => "regtest",
+        }
+    }
+
+    pub fn default_rpc_port(self) -> u16 {
+        match self {
+            Chain::Main => 8332,
+            Chain::Testnet3 => 18332,
+            Chain::Testnet4 => 48332,
+            Chain::Signet => 38332,
+            Chain::Regtest => 18443,
+        }
+    }
+}
+
+// testnet4 shares address encoding (`tb1`/`m`/`n`/`2`) with testnet3.
+impl From<Chain> for Network {
+    fn from(chain: Chain) -> Network {
+        match chain {
+            Chain::Main => Network::Bitcoin,
+            Chain::Testnet3 | Chain::Testnet4 => Network::Testnet,
+            Chain::Signet => Network::Signet,
+            Chain::Regtest => Network::Regtest,
+        }
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.core_name())
+    }
+}
+
+impl FromStr for Chain {
+    type Err = NetworkError;
+
+    // Accepts Bitcoin Core's names as well as rust-bitcoin's (`bitcoin`, `testnet`).
+    fn from_str(s: &str) -> Result<Chain, NetworkError> {
+        match s {
+            "main" | "mainnet" | "bitcoin" => Ok(Chain::Main),
+            "test" | "testnet" | "testnet3" => Ok(Chain::Testnet3),
+            "testnet4" => Ok(Chain::Testnet4),
+            "signet" => Ok(Chain::Signet),
+            "regtest" => Ok(Chain::Regtest),
+            _ => Err(NetworkError::UnknownChain(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Rpc(bitcoincore_rpc::Error),
+    UnknownChain(String),
+    Mismatch { expected: Chain, actual: Chain },
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::Rpc(e) => write!(f, "cannot query node chain: {e}"),
+            NetworkError::UnknownChain(name) => write!(f, "unknown chain {name:?}"),
+            NetworkError::Mismatch { expected, actual } => {
+                write!(f, "configured for {expected} but the node runs {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<bitcoincore_rpc::Error> for NetworkError {
+    fn from(e: bitcoincore_rpc::Error) -> NetworkError {
+        NetworkError::Rpc(e)
+    }
+}
+
+/// Asks the node which chain it runs on. If `expected` is set, the node must agree.
+///
+/// We read `chain` as a plain string instead of using `get_blockchain_info`,
+/// whose typed result fails to deserialize on chains the library doesn't know.
+pub fn detect(rpc: &Client, expected: Option<Chain>) -> Result<Chain, NetworkError> {
+    #[derive(Deserialize)]
+    struct ChainInfo {
+        chain: String,
+    }
+    let info = rpc.call::<ChainInfo>("getblockchaininfo", &[])?;
+    let actual = info.chain.parse::<Chain>()?;
+
+    match expected {
+        Some(expected) if expected != actual => Err(NetworkError::Mismatch { expected, actual }),
+        _ => Ok(actual),
+    }
+}
@@ -0,0 +1,189 @@
+use crate::auth::{self, Credentials};
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::network::{self, Chain, NetworkError};
+use crate::passphrase::{Passphrase, PassphraseError};
+use crate::wallet::{self, Reconciled, Unlocked, WalletHandle, WalletSpec};
+use bitcoincore_rpc::bitcoin::{BlockHash, Network};
+use bitcoincore_rpc::{Client, RpcApi};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A connection to a Bitcoin Core node, bound to the chain it runs on.
+pub struct Node {
+    rpc: Client,
+    config: Config,
+    credentials: Credentials,
+    chain: Chain,
+    // Read from `config.passphrase_from` on first use for each wallet, so a
+    // prompt comes up once per wallet and names it
+    passphrases: RefCell<HashMap<String, Rc<Passphrase>>>,
+}
+
+impl Node {
+    /// Connects with the resolved credentials and detects the node's chain.
+    pub fn connect(config: Config) -> Result<Node> {
+        // Prefer the node's cookie file over user/password
+        let credentials = Credentials::resolve(&config)?;
+        let rpc = credentials.connect(&config.rpc_url())?;
+
+        // This is the first request, so a 401 here means bad credentials.
+        let chain = network::detect(&rpc, config.network).map_err(|e| match e {
+            NetworkError::Rpc(e) if auth::is_unauthorized(&e) => Error::Unauthorized {
+                method: credentials.method.clone(),
+                skipped_cookie: credentials.skipped_cookie.clone(),
+            },
+            e => Error::Network(e),
+        })?;
+
+        Ok(Node {
+            rpc,
+            config,
+            credentials,
+            chain,
+            passphrases: RefCell::default(),
+        })
+    }
+
+    /// Client for node-level (non-wallet) RPC calls.
+    pub fn rpc(&self) -> &Client {
+        &self.rpc
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The credentials in use, including a cookie file skipped on the way.
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    pub fn chain(&self) -> Chain {
+        self.chain
+    }
+
+    /// Network used to parse and render addresses on this node's chain.
+    pub fn network(&self) -> Network {
+        Network::from(self.chain)
+    }
+
+    /// Creates and/or loads `wallet_name` as needed and returns a handle bound to it.
+    /// With a passphrase source configured, a created wallet is encrypted.
+    pub fn ensure_wallet(&self, wallet_name: &str) -> Result<WalletHandle> {
+        Ok(self.reconcile(wallet_name, None)?.0)
+    }
+
+    /// Like [`Node::ensure_wallet`], but creates the wallet as `spec` describes,
+    /// and fails with [`Error::WalletMismatch`] if an existing one differs from it.
+    pub fn ensure_wallet_with(&self, wallet_name: &str, spec: &WalletSpec) -> Result<WalletHandle> {
+        let (wallet, outcome) = self.reconcile(wallet_name, Some(spec))?;
+        outcome.matched(wallet_name)?;
+        Ok(wallet)
+    }
+
+    /// Loads `wallet_name` if needed and reports what was done, like
+    /// [`Node::reconcile`] but never creating it: a wallet the node doesn't
+    /// have fails with [`Error::WalletNotFound`].
+    pub fn load_wallet(&self, wallet_name: &str) -> Result<(WalletHandle, Reconciled)> {
+        // Wallets loaded by path are not in the wallet dir
+        if !self.rpc.list_wallets()?.iter().any(|w| w == wallet_name)
+            && !self.rpc.list_wallet_dir()?.iter().any(|w| w == wallet_name)
+        {
+            return Err(Error::WalletNotFound(wallet_name.to_owned()));
+        }
+        self.reconcile(wallet_name, None)
+    }
+
+    /// Loads or creates `wallet_name` like [`Node::ensure_wallet`] and reports
+    /// what it did, see [`wallet::reconcile`]. A mismatch with `spec` is an
+    /// outcome here, not an error.
+    pub fn reconcile(
+        &self,
+        wallet_name: &str,
+        spec: Option<&WalletSpec>,
+    ) -> Result<(WalletHandle, Reconciled)> {
+        // Only read (or prompted for) when the wallet is created
+        let read = || self.passphrase(wallet_name);
+        let (client, outcome) = wallet::reconcile(
+            &self.rpc,
+            &self.config,
+            &self.credentials,
+            wallet_name,
+            spec,
+            self.config.passphrase_from.is_some().then_some(&read as _),
+        )?;
+        Ok((
+            WalletHandle::new(wallet_name, client, self.network()),
+            outcome,
+        ))
+    }
+
+    /// Unlocks `wallet` for signing if it is encrypted, for at most the
+    /// configured unlock timeout. Keep the guard for as long as keys are needed;
+    /// dropping it locks the wallet again. `None` if the wallet isn't encrypted.
+    pub fn unlock<'a>(&self, wallet: &'a WalletHandle) -> Result<Option<Unlocked<'a>>> {
+        if !wallet.is_encrypted()? {
+            return Ok(None);
+        }
+        let passphrase = self.passphrase(wallet.name())?;
+        Ok(Some(
+            wallet.unlock(&passphrase, self.config.unlock_timeout)?,
+        ))
+    }
+
+    /// The passphrase of `wallet` from the configured source, read once per
+    /// wallet and run. `wallet` is named in the prompt and errors.
+    pub fn passphrase(&self, wallet: &str) -> Result<Rc<Passphrase>> {
+        if let Some(passphrase) = self.passphrases.borrow().get(wallet) {
+            return Ok(Rc::clone(passphrase));
+        }
+        let source = self
+            .config
+            .passphrase_from
+            .as_ref()
+            .ok_or_else(|| PassphraseError::NotConfigured(wallet.to_owned()))?;
+        let passphrase = Rc::new(source.read(wallet)?);
+        self.passphrases
+            .borrow_mut()
+            .insert(wallet.to_owned(), Rc::clone(&passphrase));
+        Ok(passphrase)
+    }
+
+    /// Handle for a wallet that is already loaded, skipping the create/load checks.
+    pub fn loaded_wallet(&self, wallet_name: &str) -> Result<WalletHandle> {
+        let client = self
+            .credentials
+            .connect(&self.config.wallet_url(wallet_name))?;
+        Ok(WalletHandle::new(wallet_name, client, self.network()))
+    }
+
+    /// Handles for every wallet currently loaded on the node.
+    pub fn loaded_wallets(&self) -> Result<Vec<WalletHandle>> {
+        self.rpc
+            .list_wallets()?
+            .iter()
+            .map(|name| self.loaded_wallet(name))
+            .collect()
+    }
+
+    /// Wallets in the node's wallet directory, each with whether it is currently loaded.
+    pub fn list_wallets(&self) -> Result<Vec<(String, bool)>> {
+        let loaded = self.rpc.list_wallets()?;
+        let wallets = self
+            .rpc
+            .list_wallet_dir()?
+            .into_iter()
+            .map(|name| {
+                let is_loaded = loaded.contains(&name);
+                (name, is_loaded)
+            })
+            .collect();
+        Ok(wallets)
+    }
+
+    pub fn block_height(&self, block_hash: &BlockHash) -> Result<usize> {
+        Ok(self.rpc.get_block_info(block_hash)?.height)
+    }
+}
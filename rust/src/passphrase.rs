@@ -0,0 +1,174 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::{env, fmt};
+
+/// Where the wallet passphrase is read from. The passphrase itself never goes
+/// into the config, flags or environment listing, only where to find it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassphraseSource {
+    /// Ask on the terminal, without echo.
+    Prompt,
+    /// The named environment variable.
+    Env(String),
+    /// The first line read from an inherited file descriptor, e.g. `3<secret.txt`.
+    Fd(u32),
+}
+
+impl FromStr for PassphraseSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PassphraseSource, String> {
+        match s.split_once(':') {
+            None if s == "prompt" => Ok(PassphraseSource::Prompt),
+            Some(("env", var)) if !var.is_empty() => Ok(PassphraseSource::Env(var.to_owned())),
+            Some(("fd", fd)) => fd
+                .parse()
+                .map(PassphraseSource::Fd)
+                .map_err(|_| format!("invalid file descriptor {fd:?}")),
+            _ => Err(format!(
+                "unknown passphrase source {s:?} (expected prompt, env:<VAR> or fd:<N>)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PassphraseSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PassphraseSource::Prompt => f.write_str("prompt"),
+            PassphraseSource::Env(var) => write!(f, "env:{var}"),
+            PassphraseSource::Fd(fd) => write!(f, "fd:{fd}"),
+        }
+    }
+}
+
+/// Why no passphrase could be read.
+#[derive(Debug)]
+pub enum PassphraseError {
+    /// The source could not be read (unset variable, closed descriptor, no terminal).
+    Unavailable {
+        source: PassphraseSource,
+        reason: String,
+    },
+    Empty(PassphraseSource),
+    /// A wallet needs a passphrase but no source is configured.
+    NotConfigured(String),
+}
+
+impl fmt::Display for PassphraseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PassphraseError::Unavailable { source, reason } => {
+                write!(
+                    f,
+                    "cannot read the wallet passphrase from {source}: {reason}"
+                )
+            }
+            PassphraseError::Empty(source) => write!(f, "empty wallet passphrase from {source}"),
+            PassphraseError::NotConfigured(wallet) => write!(
+                f,
+                "wallet {wallet} needs a passphrase; set --passphrase-from"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PassphraseError {}
+
+/// A wallet passphrase. It has no `Display`, its `Debug` is redacted, and its
+/// memory is overwritten when dropped, so it doesn't end up in output or logs.
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: String) -> Passphrase {
+        Passphrase(passphrase)
+    }
+
+    /// The passphrase, for handing to the node.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Passphrase(<redacted>)")
+    }
+}
+
+impl Drop for Passphrase {
+    fn drop(&mut self) {
+        // Best effort: copies made while reading or sending it are out of reach
+        let mut bytes = std::mem::take(&mut self.0).into_bytes();
+        bytes.fill(0);
+        std::hint::black_box(&bytes);
+    }
+}
+
+impl PassphraseSource {
+    /// Reads the passphrase; `wallet` is only named in the prompt.
+    pub fn read(&self, wallet: &str) -> Result<Passphrase, PassphraseError> {
+        let unavailable = |reason: String| PassphraseError::Unavailable {
+            source: self.clone(),
+            reason,
+        };
+        let passphrase = match self {
+            PassphraseSource::Prompt => prompt(wallet).map_err(|e| unavailable(e.to_string()))?,
+            PassphraseSource::Env(var) => env::var(var).map_err(|e| unavailable(e.to_string()))?,
+            PassphraseSource::Fd(fd) => {
+                // Linux and macOS expose inherited descriptors here, which avoids unsafe code
+                let file =
+                    File::open(format!("/dev/fd/{fd}")).map_err(|e| unavailable(e.to_string()))?;
+                first_line(BufReader::new(file)).map_err(|e| unavailable(e.to_string()))?
+            }
+        };
+        if passphrase.is_empty() {
+            return Err(PassphraseError::Empty(self.clone()));
+        }
+        Ok(Passphrase(passphrase))
+    }
+}
+
+fn first_line<R: BufRead>(mut reader: R) -> io::Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+// Asks on the controlling terminal with echo turned off by `stty`.
+fn prompt(wallet: &str) -> io::Result<String> {
+    let tty = File::options().read(true).write(true).open("/dev/tty")?;
+    let stty = |arg: &str| {
+        let status = Command::new("stty")
+            .arg(arg)
+            .stdin(tty.try_clone()?)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("stty {arg} failed ({status})")))
+        }
+    };
+
+    write!(&tty, "Passphrase for wallet {wallet}: ")?;
+    (&tty).flush()?;
+    // Never read with echo on; if turning it off half-worked, turn it back on
+    if let Err(e) = stty("-echo") {
+        let _ = stty("echo");
+        return Err(e);
+    }
+    let line = first_line(BufReader::new(&tty));
+    // Echo is restored before any error is returned
+    let restored = stty("echo");
+    let newline = writeln!(&tty);
+    let line = line?;
+    restored?;
+    newline?;
+    Ok(line)
+}
@@ -0,0 +1,216 @@
+use crate::error::Result;
+use crate::send::SendOptions;
+use crate::wallet::WalletHandle;
+use bitcoincore_rpc::bitcoin::{Address, Amount, Txid};
+use bitcoincore_rpc::{Client, RpcApi};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::{fmt, fs};
+
+/// A funded, unsigned PSBT from `walletcreatefundedpsbt`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Funded {
+    pub psbt: String,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    /// Position of the change output, `None` if there is none.
+    #[serde(rename = "changepos", deserialize_with = "change_position")]
+    pub change_position: Option<u32>,
+}
+
+// Core reports "no change output" as -1.
+fn change_position<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> std::result::Result<Option<u32>, D::Error> {
+    let position = i64::deserialize(d)?;
+    Ok(u32::try_from(position).ok())
+}
+
+/// A PSBT after a wallet added what it knows and, if asked to, its signatures.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Processed {
+    pub psbt: String,
+    /// Every input is signed.
+    pub complete: bool,
+}
+
+/// Result of `finalizepsbt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finalized {
+    /// The network-serialized transaction, in hex, ready to broadcast.
+    Complete(String),
+    /// Some input still lacks signatures; the PSBT with whatever could be finalized.
+    Incomplete(String),
+}
+
+/// What a PSBT pays and which role has to act on it next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub txid: Txid,
+    pub inputs: usize,
+    pub outputs: Vec<SummaryOutput>,
+    /// Known once every input's previous output is in the PSBT.
+    pub fee: Option<Amount>,
+    pub estimated_vsize: Option<u64>,
+    /// sat/vB.
+    pub estimated_fee_rate: Option<f64>,
+    /// `creator`, `updater`, `signer`, `finalizer` or `extractor`.
+    pub next: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryOutput {
+    /// `None` for scripts that don't encode an address.
+    pub address: Option<String>,
+    pub amount: Amount,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "txid: {}", self.txid)?;
+        writeln!(f, "inputs: {}", self.inputs)?;
+        writeln!(f, "outputs:")?;
+        for (vout, output) in self.outputs.iter().enumerate() {
+            let address = output.address.as_deref().unwrap_or("-");
+            writeln!(f, "  {vout}: {address} {}", output.amount)?;
+        }
+        match self.fee {
+            Some(fee) => writeln!(f, "fee: {fee}")?,
+            None => writeln!(f, "fee: unknown")?,
+        }
+        if let (Some(vsize), Some(rate)) = (self.estimated_vsize, self.estimated_fee_rate) {
+            writeln!(f, "estimated size: {vsize} vB ({rate:.2} sat/vB)")?;
+        }
+        write!(f, "next: {}", self.next)
+    }
+}
+
+/// Creates a PSBT paying each `(address, amount)` from `wallet`, with inputs
+/// and change chosen by the wallet. Nothing is signed yet.
+///
+/// This is the first step of sending through a PSBT, so that a payment can be
+/// reviewed, or signed by another wallet, before it is broadcast:
+/// `create`, [`inspect`], [`sign`], [`finalize`], [`broadcast`]. PSBTs are
+/// base64, as Core's RPCs take them; [`save`] and [`load`] keep them in a file
+/// between steps.
+pub fn create(
+    wallet: &WalletHandle,
+    recipients: &[(Address, Amount)],
+    options: &SendOptions,
+) -> Result<Funded> {
+    let args = options.funded_psbt_args(recipients);
+    Ok(wallet.client().call("walletcreatefundedpsbt", &args)?)
+}
+
+/// Decodes `psbt` on the node: outputs, fee and the next role to act on it.
+pub fn inspect(rpc: &Client, psbt: &str) -> Result<Summary> {
+    #[derive(Deserialize)]
+    struct Decoded {
+        tx: DecodedTx,
+        #[serde(default, with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc::opt")]
+        fee: Option<Amount>,
+    }
+    #[derive(Deserialize)]
+    struct DecodedTx {
+        txid: Txid,
+        vin: Vec<Value>,
+        vout: Vec<DecodedOutput>,
+    }
+    #[derive(Deserialize)]
+    struct DecodedOutput {
+        #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+        value: Amount,
+        #[serde(rename = "scriptPubKey")]
+        script_pubkey: DecodedScript,
+    }
+    #[derive(Deserialize)]
+    struct DecodedScript {
+        address: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Analysis {
+        estimated_vsize: Option<u64>,
+        // BTC/kvB
+        #[serde(default, with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc::opt")]
+        estimated_feerate: Option<Amount>,
+        next: String,
+    }
+
+    let decoded: Decoded = rpc.call("decodepsbt", &[psbt.into()])?;
+    let analysis: Analysis = rpc.call("analyzepsbt", &[psbt.into()])?;
+
+    Ok(Summary {
+        txid: decoded.tx.txid,
+        inputs: decoded.tx.vin.len(),
+        outputs: decoded
+            .tx
+            .vout
+            .into_iter()
+            .map(|o| SummaryOutput {
+                address: o.script_pubkey.address,
+                amount: o.value,
+            })
+            .collect(),
+        fee: decoded.fee,
+        estimated_vsize: analysis.estimated_vsize,
+        estimated_fee_rate: analysis
+            .estimated_feerate
+            .map(|rate| rate.to_sat() as f64 / 1000.0),
+        next: analysis.next,
+    })
+}
+
+/// Has `wallet` fill in what it knows about the inputs and sign the ones it can.
+/// Finalizing is left to [`finalize`].
+pub fn sign(wallet: &WalletHandle, psbt: &str) -> Result<Processed> {
+    // psbt, sign, sighashtype, bip32derivs, finalize
+    let args = [
+        psbt.into(),
+        true.into(),
+        "DEFAULT".into(),
+        true.into(),
+        false.into(),
+    ];
+    Ok(wallet.client().call("walletprocesspsbt", &args)?)
+}
+
+/// Finalizes the inputs' scripts and, once all are, extracts the transaction.
+pub fn finalize(rpc: &Client, psbt: &str) -> Result<Finalized> {
+    #[derive(Deserialize)]
+    struct RawFinalized {
+        psbt: Option<String>,
+        hex: Option<String>,
+        complete: bool,
+    }
+    let finalized: RawFinalized = rpc.call("finalizepsbt", &[psbt.into(), true.into()])?;
+    match finalized {
+        RawFinalized {
+            complete: true,
+            hex: Some(hex),
+            ..
+        } => Ok(Finalized::Complete(hex)),
+        RawFinalized {
+            complete: false,
+            psbt: Some(psbt),
+            ..
+        } => Ok(Finalized::Incomplete(psbt)),
+        _ => Err(bitcoincore_rpc::Error::UnexpectedStructure.into()),
+    }
+}
+
+/// Broadcasts a finalized transaction (hex).
+pub fn broadcast(rpc: &Client, tx_hex: &str) -> Result<Txid> {
+    Ok(rpc.send_raw_transaction(tx_hex)?)
+}
+
+/// Writes a PSBT or transaction hex to `path`, one line.
+pub fn save(path: &Path, data: &str) -> Result<()> {
+    fs::write(path, format!("{data}\n"))?;
+    Ok(())
+}
+
+/// Reads what [`save`] wrote (or any file holding a base64 PSBT or hex transaction).
+pub fn load(path: &Path) -> Result<String> {
+    Ok(fs::read_to_string(path)?.trim().to_owned())
+}
@@ -0,0 +1,456 @@
+use crate::error::{Error, Result};
+use crate::node::Node;
+use crate::wallet::{OwnedAddress, WalletHandle};
+use bitcoincore_rpc::bitcoin::{Address, Amount, BlockHash, OutPoint, Script, SignedAmount, Txid};
+use bitcoincore_rpc::RpcApi;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// One spent output, resolved through its parent transaction.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportInput {
+    pub outpoint: OutPoint,
+    /// `None` for scripts that don't encode an address.
+    pub address: Option<Address>,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub amount: Amount,
+    pub script_type: &'static str,
+    /// Loaded wallet the address belongs to, if any.
+    pub wallet: Option<String>,
+    /// The owning wallet's label for the address.
+    pub label: Option<String>,
+}
+
+/// Role of an output from the sending wallet's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputKind {
+    /// Pays an address the sender doesn't use for change (including its own receive addresses).
+    Recipient,
+    /// Pays one of the sender's internal (change) addresses.
+    Change,
+    /// `OP_RETURN` data carrier; unspendable.
+    Data,
+    /// A script that doesn't encode an address.
+    NonStandard,
+}
+
+impl fmt::Display for OutputKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OutputKind::Recipient => "recipient",
+            OutputKind::Change => "change",
+            OutputKind::Data => "data",
+            OutputKind::NonStandard => "non_standard",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportOutput {
+    pub vout: u32,
+    pub address: Option<Address>,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub amount: Amount,
+    pub script_type: &'static str,
+    pub kind: OutputKind,
+    /// Loaded wallet the address belongs to, if any.
+    pub wallet: Option<String>,
+    /// The owning wallet's label for the address.
+    pub label: Option<String>,
+}
+
+/// Details of a confirmed payment, as required for `out.txt`.
+///
+/// Amounts serialize in BTC. `fee` is negative, as `gettransaction` reports it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionReport {
+    pub txid: Txid,
+    pub inputs: Vec<ReportInput>,
+    pub outputs: Vec<ReportOutput>,
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    pub fee: SignedAmount,
+    pub block_height: usize,
+    pub block_hash: BlockHash,
+}
+
+impl TransactionReport {
+    /// Extracts the report for `txid`, sent from `sender`.
+    pub fn extract(node: &Node, sender: &WalletHandle, txid: &Txid) -> Result<TransactionReport> {
+        let network = node.network();
+
+        // Extract all required transaction details
+        let tx_details = sender.client().get_transaction(txid, Some(true))?;
+        let tx = tx_details.transaction()?; // Fully decoded transaction
+        let fee = tx_details.fee.unwrap_or(SignedAmount::ZERO);
+
+        // Get block info
+        let block_hash = tx_details
+            .info
+            .blockhash
+            .ok_or(Error::TxUnconfirmed(*txid))?;
+        let block_height = node.block_height(&block_hash)?;
+
+        // Extract input info: resolve every spent output through its parent transaction
+        // and find which loaded wallet (the sender first) owns it, and its label there
+        let mut wallets = node.loaded_wallets()?;
+        if let Some(i) = wallets.iter().position(|w| w.name() == sender.name()) {
+            wallets.swap(0, i);
+        }
+        let mut inputs = Vec::with_capacity(tx.input.len());
+        for input in &tx.input {
+            let outpoint = input.previous_output;
+            let parent = sender.client().get_raw_transaction(&outpoint.txid, None)?;
+            let prevout =
+                parent
+                    .output
+                    .get(outpoint.vout as usize)
+                    .ok_or(Error::MissingPrevout {
+                        txid: outpoint.txid,
+                        vout: outpoint.vout,
+                    })?;
+
+            let address = Address::from_script(&prevout.script_pubkey, network).ok();
+            let (wallet, owned) = owner(&wallets, &address)?.unzip();
+
+            inputs.push(ReportInput {
+                outpoint,
+                address,
+                amount: prevout.value,
+                script_type: script_type(&prevout.script_pubkey),
+                wallet,
+                label: owned.and_then(|o| o.label),
+            });
+        }
+
+        // Extract output info: the sender's wallet tells us which outputs are its change
+        let mut outputs = Vec::with_capacity(tx.output.len());
+        for (vout, out) in tx.output.iter().enumerate() {
+            let address = Address::from_script(&out.script_pubkey, network).ok();
+            let (wallet, owned) = owner(&wallets, &address)?.unzip();
+            let kind = match (&address, &wallet, &owned) {
+                (Some(_), Some(wallet), Some(owned)) if wallet == sender.name() && owned.change => {
+                    OutputKind::Change
+                }
+                (Some(_), _, _) => OutputKind::Recipient,
+                (None, _, _) if out.script_pubkey.is_op_return() => OutputKind::Data,
+                (None, _, _) => OutputKind::NonStandard,
+            };
+            outputs.push(ReportOutput {
+                vout: vout as u32,
+                address,
+                amount: out.value,
+                script_type: script_type(&out.script_pubkey),
+                kind,
+                wallet,
+                label: owned.and_then(|o| o.label),
+            });
+        }
+
+        Ok(TransactionReport {
+            txid: *txid,
+            inputs,
+            outputs,
+            fee,
+            block_height,
+            block_hash,
+        })
+    }
+
+    /// Sum of all spent outputs.
+    pub fn input_amount(&self) -> Amount {
+        self.inputs.iter().map(|input| input.amount).sum()
+    }
+
+    /// Distinct input addresses, in input order, comma-separated.
+    pub fn input_addresses(&self) -> String {
+        join_addresses(self.inputs.iter().map(|i| (&i.address, i.script_type)))
+    }
+
+    pub fn outputs_of(&self, kind: OutputKind) -> impl Iterator<Item = &ReportOutput> {
+        self.outputs.iter().filter(move |o| o.kind == kind)
+    }
+
+    /// Sum of the outputs of `kind`.
+    pub fn output_amount(&self, kind: OutputKind) -> Amount {
+        self.outputs_of(kind).map(|o| o.amount).sum()
+    }
+
+    /// Distinct labels of the input addresses, comma-separated.
+    pub fn input_labels(&self) -> String {
+        join_labels(self.inputs.iter().map(|i| &i.label))
+    }
+
+    /// Distinct labels of the outputs of `kind`, comma-separated.
+    pub fn output_labels(&self, kind: OutputKind) -> String {
+        join_labels(self.outputs_of(kind).map(|o| &o.label))
+    }
+
+    /// Distinct addresses of the outputs of `kind`, comma-separated, or `N/A` if there are none.
+    pub fn output_addresses(&self, kind: OutputKind) -> String {
+        let addresses = join_addresses(self.outputs_of(kind).map(|o| (&o.address, o.script_type)));
+        if addresses.is_empty() {
+            "N/A".to_owned()
+        } else {
+            addresses
+        }
+    }
+
+    /// Writes the report in `format`.
+    pub fn write<W: Write>(&self, format: ReportFormat, mut out: W) -> Result<()> {
+        match format {
+            ReportFormat::Text => self.write_text(out)?,
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut out, self)?;
+                writeln!(out)?;
+            }
+            ReportFormat::Csv => self.write_csv(out)?,
+            ReportFormat::Markdown => self.write_markdown(out)?,
+        }
+        Ok(())
+    }
+
+    /// Writes the ten-line format given in readme.md. Several recipient or
+    /// change outputs are listed comma-separated on one line, with their amounts summed.
+    pub fn write_text<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}", self.txid)?;
+        writeln!(out, "{}", self.input_addresses())?;
+        writeln!(out, "{}", self.input_amount().to_btc())?;
+        writeln!(out, "{}", self.output_addresses(OutputKind::Recipient))?;
+        writeln!(
+            out,
+            "{}",
+            self.output_amount(OutputKind::Recipient).to_btc()
+        )?;
+        writeln!(out, "{}", self.output_addresses(OutputKind::Change))?;
+        writeln!(out, "{}", self.output_amount(OutputKind::Change).to_btc())?;
+        writeln!(out, "{}", self.fee.to_btc())?;
+        writeln!(out, "{}", self.block_height)?;
+        writeln!(out, "{}", self.block_hash)?;
+        Ok(())
+    }
+
+    // Summary fields shared by the CSV and Markdown formats: the out.txt fields
+    // in order, then the labels of the input, recipient and change addresses.
+    fn summary(&self) -> [(&'static str, String); 13] {
+        [
+            ("txid", self.txid.to_string()),
+            ("input_addresses", self.input_addresses()),
+            ("input_amount", self.input_amount().to_btc().to_string()),
+            (
+                "recipient_addresses",
+                self.output_addresses(OutputKind::Recipient),
+            ),
+            (
+                "recipient_amount",
+                self.output_amount(OutputKind::Recipient)
+                    .to_btc()
+                    .to_string(),
+            ),
+            (
+                "change_addresses",
+                self.output_addresses(OutputKind::Change),
+            ),
+            (
+                "change_amount",
+                self.output_amount(OutputKind::Change).to_btc().to_string(),
+            ),
+            ("fee", self.fee.to_btc().to_string()),
+            ("block_height", self.block_height.to_string()),
+            ("block_hash", self.block_hash.to_string()),
+            ("input_labels", self.input_labels()),
+            (
+                "recipient_labels",
+                self.output_labels(OutputKind::Recipient),
+            ),
+            ("change_labels", self.output_labels(OutputKind::Change)),
+        ]
+    }
+
+    /// Writes a header row and one data row with the summary fields.
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let summary = self.summary();
+        let header: Vec<&str> = summary.iter().map(|(name, _)| *name).collect();
+        let row: Vec<String> = summary.iter().map(|(_, value)| csv_field(value)).collect();
+        writeln!(out, "{}", header.join(","))?;
+        writeln!(out, "{}", row.join(","))
+    }
+
+    /// Writes a summary table followed by input and output tables.
+    pub fn write_markdown<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "| Field | Value |")?;
+        writeln!(out, "| --- | --- |")?;
+        for (name, value) in self.summary() {
+            writeln!(out, "| {name} | {} |", markdown_cell(&value))?;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "| Input | Address | Label | Amount (BTC) | Type | Wallet |"
+        )?;
+        writeln!(out, "| --- | --- | --- | --- | --- | --- |")?;
+        for input in &self.inputs {
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                input.outpoint,
+                display_address(&input.address),
+                markdown_cell(input.label.as_deref().unwrap_or("-")),
+                input.amount.to_btc(),
+                input.script_type,
+                markdown_cell(input.wallet.as_deref().unwrap_or("-"))
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "| Output | Address | Label | Amount (BTC) | Type | Kind | Wallet |"
+        )?;
+        writeln!(out, "| --- | --- | --- | --- | --- | --- | --- |")?;
+        for output in &self.outputs {
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} | {} |",
+                output.vout,
+                display_address(&output.address),
+                markdown_cell(output.label.as_deref().unwrap_or("-")),
+                output.amount.to_btc(),
+                output.script_type,
+                output.kind,
+                markdown_cell(output.wallet.as_deref().unwrap_or("-"))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Layout used to write a [`TransactionReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// The ten positional lines of `out.txt`.
+    #[default]
+    Text,
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<ReportFormat, String> {
+        match s {
+            "text" | "txt" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            _ => Err(format!("unknown report format {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ReportFormat::Text => "text",
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Markdown => "markdown",
+        })
+    }
+}
+
+/// Short name of a script's template, as used by Bitcoin Core's `scriptPubKey.type`.
+pub fn script_type(script: &Script) -> &'static str {
+    if script.is_p2pkh() {
+        "pubkeyhash"
+    } else if script.is_p2sh() {
+        "scripthash"
+    } else if script.is_p2wpkh() {
+        "witness_v0_keyhash"
+    } else if script.is_p2wsh() {
+        "witness_v0_scripthash"
+    } else if script.is_p2tr() {
+        "witness_v1_taproot"
+    } else if script.is_p2pk() {
+        "pubkey"
+    } else if script.is_op_return() {
+        "nulldata"
+    } else if script.is_witness_program() {
+        "witness_unknown"
+    } else {
+        "nonstandard"
+    }
+}
+
+// Distinct addresses in order, comma-separated. Scripts without an address
+// show as their type, e.g. `<nulldata>`.
+fn join_addresses<'a, I>(addresses: I) -> String
+where
+    I: Iterator<Item = (&'a Option<Address>, &'static str)>,
+{
+    let mut distinct: Vec<String> = Vec::new();
+    for (address, script_type) in addresses {
+        let address = match address {
+            Some(address) => address.to_string(),
+            None => format!("<{script_type}>"),
+        };
+        if !distinct.contains(&address) {
+            distinct.push(address);
+        }
+    }
+    distinct.join(",")
+}
+
+// Finds the first of `wallets` that owns `address`.
+fn owner(
+    wallets: &[WalletHandle],
+    address: &Option<Address>,
+) -> Result<Option<(String, OwnedAddress)>> {
+    if let Some(address) = address {
+        for wallet in wallets {
+            if let Some(owned) = wallet.lookup(address)? {
+                return Ok(Some((wallet.name().to_owned(), owned)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// Distinct labels in order, comma-separated; unlabelled addresses are left out.
+fn join_labels<'a, I>(labels: I) -> String
+where
+    I: Iterator<Item = &'a Option<String>>,
+{
+    let mut distinct: Vec<&str> = Vec::new();
+    for label in labels.flatten() {
+        if !distinct.contains(&label.as_str()) {
+            distinct.push(label);
+        }
+    }
+    distinct.join(",")
+}
+
+fn display_address(address: &Option<Address>) -> String {
+    address
+        .as_ref()
+        .map_or_else(|| "-".to_owned(), |a| a.to_string())
+}
+
+// Quotes a CSV field if it contains a separator, quote or newline.
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+// Escapes pipes, which would end a Markdown table cell, and keeps the cell on one line.
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
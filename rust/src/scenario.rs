@@ -0,0 +1,348 @@
+use crate::error::{Error, Result};
+use crate::mining;
+use crate::node::Node;
+use crate::send::{EstimateMode, SendOptions};
+use crate::wallet::{MINING_REWARD_LABEL, RECEIVED_LABEL};
+use bitcoincore_rpc::bitcoin::{Amount, Txid};
+use bitcoincore_rpc::RpcApi;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{fmt, fs, thread};
+
+// How often `wait-for-mempool` polls the node.
+const MEMPOOL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+fn default_mempool_timeout() -> u64 {
+    30
+}
+
+/// A scripted multi-wallet flow, read from a TOML file:
+///
+/// ```toml
+/// name = "Miner pays Trader"
+///
+/// [[step]]
+/// action = "create-wallet"
+/// wallet = "Miner"
+///
+/// [[step]]
+/// action = "send"
+/// from = "Miner"
+/// to = "Trader"
+/// amount = 20.0
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(rename = "step", default)]
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Step {
+    /// Create the wallet if needed and load it.
+    CreateWallet { wallet: String },
+    /// Mine `blocks` blocks to a new address of `to`.
+    Mine { blocks: u64, to: String },
+    /// Mine one block at a time to a new address of `to` until it has a spendable balance.
+    MineUntilSpendable { to: String },
+    /// Pay `amount` BTC from `from` to a new address of `to`. The txid is
+    /// remembered under `id` (if given) for later `wait-for-mempool` steps.
+    /// `fee_rate` (sat/vB), `conf_target`, `estimate_mode`, `subtract_fee`
+    /// and `replaceable` are passed on as [`SendOptions`].
+    Send {
+        from: String,
+        to: String,
+        #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+        amount: Amount,
+        #[serde(default)]
+        id: Option<String>,
+        #[serde(default)]
+        fee_rate: Option<f64>,
+        #[serde(default)]
+        conf_target: Option<u16>,
+        #[serde(default)]
+        estimate_mode: Option<EstimateMode>,
+        #[serde(default)]
+        subtract_fee: bool,
+        #[serde(default)]
+        replaceable: Option<bool>,
+    },
+    /// Check the trusted balance of `wallet` (plus pending, if `include_pending`)
+    /// against the given bounds, in BTC.
+    AssertBalance {
+        wallet: String,
+        #[serde(default, with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc::opt")]
+        equals: Option<Amount>,
+        #[serde(default, with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc::opt")]
+        min: Option<Amount>,
+        #[serde(default, with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc::opt")]
+        max: Option<Amount>,
+        #[serde(default)]
+        include_pending: bool,
+    },
+    /// Wait until the send named `tx` (the latest send, if omitted) shows up in the mempool.
+    WaitForMempool {
+        #[serde(default)]
+        tx: Option<String>,
+        #[serde(default = "default_mempool_timeout")]
+        timeout_secs: u64,
+    },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::CreateWallet { wallet } => write!(f, "create-wallet {wallet}"),
+            Step::Mine { blocks, to } => write!(f, "mine {blocks} to {to}"),
+            Step::MineUntilSpendable { to } => write!(f, "mine-until-spendable to {to}"),
+            Step::Send {
+                from, to, amount, ..
+            } => write!(f, "send {amount} from {from} to {to}"),
+            Step::AssertBalance { wallet, .. } => write!(f, "assert-balance {wallet}"),
+            Step::WaitForMempool { tx, .. } => match tx {
+                Some(tx) => write!(f, "wait-for-mempool {tx}"),
+                None => write!(f, "wait-for-mempool"),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(PathBuf, std::io::Error),
+    Toml(PathBuf, Box<toml::de::Error>),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Io(path, e) => {
+                write!(f, "cannot read scenario {}: {e}", path.display())
+            }
+            ScenarioError::Toml(path, e) => write!(f, "invalid scenario {}: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+/// What happened to one step.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed(String),
+    Failed(String),
+    /// Not run because an earlier step failed.
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub step: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenarioReport {
+    pub name: Option<String>,
+    pub steps: Vec<StepResult>,
+}
+
+impl ScenarioReport {
+    pub fn passed(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|s| matches!(s.outcome, Outcome::Passed(_)))
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = &self.name {
+            writeln!(f, "Scenario: {name}")?;
+        }
+        for (i, result) in self.steps.iter().enumerate() {
+            let n = i + 1;
+            match &result.outcome {
+                Outcome::Passed(detail) => writeln!(f, "[{n}] ok      {}: {detail}", result.step)?,
+                Outcome::Failed(error) => writeln!(f, "[{n}] FAILED  {}: {error}", result.step)?,
+                Outcome::Skipped => writeln!(f, "[{n}] skipped {}", result.step)?,
+            }
+        }
+        let verdict = if self.passed() { "passed" } else { "failed" };
+        write!(f, "Scenario {verdict}")
+    }
+}
+
+impl Scenario {
+    pub fn from_file(path: &Path) -> std::result::Result<Scenario, ScenarioError> {
+        let text = fs::read_to_string(path).map_err(|e| ScenarioError::Io(path.to_owned(), e))?;
+        toml::from_str(&text).map_err(|e| ScenarioError::Toml(path.to_owned(), Box::new(e)))
+    }
+
+    /// Executes the steps in order. After the first failure the remaining
+    /// steps are reported as skipped.
+    pub fn run(&self, node: &Node) -> ScenarioReport {
+        let mut runner = Runner {
+            node,
+            sends: HashMap::new(),
+            last_send: None,
+        };
+        let mut failed = false;
+
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| {
+                let outcome = if failed {
+                    Outcome::Skipped
+                } else {
+                    match runner.execute(step) {
+                        Ok(detail) => Outcome::Passed(detail),
+                        Err(e) => {
+                            failed = true;
+                            Outcome::Failed(e.to_string())
+                        }
+                    }
+                };
+                StepResult {
+                    step: step.to_string(),
+                    outcome,
+                }
+            })
+            .collect();
+
+        ScenarioReport {
+            name: self.name.clone(),
+            steps,
+        }
+    }
+}
+
+// State carried between steps: txids of earlier sends.
+struct Runner<'a> {
+    node: &'a Node,
+    sends: HashMap<String, Txid>,
+    last_send: Option<Txid>,
+}
+
+impl Runner<'_> {
+    fn execute(&mut self, step: &Step) -> Result<String> {
+        match step {
+            Step::CreateWallet { wallet } => {
+                self.node.ensure_wallet(wallet)?;
+                Ok(format!("{wallet} loaded"))
+            }
+            Step::Mine { blocks, to } => {
+                let wallet = self.node.ensure_wallet(to)?;
+                let address = wallet.new_labeled_address(MINING_REWARD_LABEL)?;
+                let hashes = wallet.mine(*blocks, &address)?;
+                Ok(format!("mined {} block(s) to {address}", hashes.len()))
+            }
+            Step::MineUntilSpendable { to } => {
+                let wallet = self.node.ensure_wallet(to)?;
+                let address = wallet.new_labeled_address(MINING_REWARD_LABEL)?;
+                let maturity = mining::mine_until_spendable(&wallet, &address, |_| {})?;
+                Ok(format!(
+                    "mined {} block(s), trusted {}, immature {}",
+                    maturity.blocks_mined, maturity.trusted, maturity.immature
+                ))
+            }
+            Step::Send {
+                from,
+                to,
+                amount,
+                id,
+                fee_rate,
+                conf_target,
+                estimate_mode,
+                subtract_fee,
+                replaceable,
+            } => {
+                let options = SendOptions {
+                    fee_rate: *fee_rate,
+                    conf_target: *conf_target,
+                    estimate_mode: *estimate_mode,
+                    subtract_fee: *subtract_fee,
+                    replaceable: *replaceable,
+                    ..SendOptions::default()
+                };
+                let sender = self.node.ensure_wallet(from)?;
+                let receiver = self.node.ensure_wallet(to)?;
+                let address = receiver.new_labeled_address(RECEIVED_LABEL)?;
+                let unlocked = self.node.unlock(&sender)?;
+                let sent = sender.send_to_address(&address, *amount, &options)?;
+                drop(unlocked);
+                if let Some(id) = id {
+                    self.sends.insert(id.clone(), sent.txid);
+                }
+                self.last_send = Some(sent.txid);
+                Ok(format!("txid {sent}"))
+            }
+            Step::AssertBalance {
+                wallet,
+                equals,
+                min,
+                max,
+                include_pending,
+            } => {
+                let balances = self.node.ensure_wallet(wallet)?.client().get_balances()?;
+                let mut balance = balances.mine.trusted;
+                if *include_pending {
+                    balance += balances.mine.untrusted_pending;
+                }
+
+                let ok = equals.is_none_or(|v| balance == v)
+                    && min.is_none_or(|v| balance >= v)
+                    && max.is_none_or(|v| balance <= v);
+                if ok {
+                    Ok(format!("balance {balance}"))
+                } else {
+                    Err(Error::Assertion(format!(
+                        "balance {balance} outside bounds (equals {}, min {}, max {})",
+                        fmt_bound(equals),
+                        fmt_bound(min),
+                        fmt_bound(max)
+                    )))
+                }
+            }
+            Step::WaitForMempool { tx, timeout_secs } => {
+                // `Instant + Duration` panics past what the clock can represent
+                let deadline = Instant::now()
+                    .checked_add(Duration::from_secs(*timeout_secs))
+                    .ok_or_else(|| {
+                        Error::Assertion(format!("timeout_secs {timeout_secs} is too large"))
+                    })?;
+                let txid = match tx {
+                    Some(id) => self.sends.get(id).copied().ok_or_else(|| {
+                        Error::Assertion(format!("no earlier send step with id {id:?}"))
+                    })?,
+                    None => self
+                        .last_send
+                        .ok_or_else(|| Error::Assertion("no earlier send step".to_owned()))?,
+                };
+
+                loop {
+                    if self.node.rpc().get_raw_mempool()?.contains(&txid) {
+                        return Ok(format!("{txid} in mempool"));
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(Error::Assertion(format!(
+                            "{txid} not in mempool after {timeout_secs}s"
+                        )));
+                    }
+                    thread::sleep(MEMPOOL_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+fn fmt_bound(bound: &Option<Amount>) -> String {
+    bound.map_or_else(|| "-".to_owned(), |v| v.to_string())
+}
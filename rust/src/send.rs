@@ -0,0 +1,258 @@
+use bitcoincore_rpc::bitcoin::{Address, Amount, Txid};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::str::FromStr;
+
+/// How the node estimates a fee for a confirmation target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EstimateMode {
+    /// Lower fee, reacting faster to short-term drops in the fee market.
+    Economical,
+    /// Higher fee, less likely to be outbid over the target's whole window.
+    Conservative,
+}
+
+impl EstimateMode {
+    /// Name as accepted by Core's `estimate_mode` argument.
+    pub fn core_name(&self) -> &'static str {
+        match self {
+            EstimateMode::Economical => "economical",
+            EstimateMode::Conservative => "conservative",
+        }
+    }
+}
+
+impl fmt::Display for EstimateMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.core_name())
+    }
+}
+
+impl FromStr for EstimateMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EstimateMode, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "economical" => Ok(EstimateMode::Economical),
+            "conservative" => Ok(EstimateMode::Conservative),
+            _ => Err(format!("unknown estimate mode {s:?}")),
+        }
+    }
+}
+
+/// Fee and funding settings for a payment, built like
+/// `SendOptions::new().conf_target(6).subtract_fee(true)`. Anything left unset is
+/// chosen by the node or wallet; the fee comes from the node's estimates, or
+/// `fallbackfee` when it has none (as on a fresh regtest chain).
+///
+/// `sendtoaddress` only takes the fee settings and `replaceable`; the other
+/// funding settings apply to `send`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SendOptions {
+    /// sat/vB.
+    pub fee_rate: Option<f64>,
+    pub conf_target: Option<u16>,
+    pub estimate_mode: Option<EstimateMode>,
+    /// Take the fee out of the recipients' amounts, split equally.
+    pub subtract_fee: bool,
+    /// Whether the wallet may add inputs beyond any preselected ones.
+    pub add_inputs: Option<bool>,
+    pub change_address: Option<Address>,
+    /// Index of the change output; random by default.
+    pub change_position: Option<u32>,
+    /// Also spend outputs of watch-only addresses.
+    pub include_watching: Option<bool>,
+    pub locktime: Option<u32>,
+    /// Signal BIP 125 replaceability.
+    pub replaceable: Option<bool>,
+}
+
+impl SendOptions {
+    pub fn new() -> SendOptions {
+        SendOptions::default()
+    }
+
+    /// Pays exactly `sat_per_vb` sat/vB. Core rejects this together with a confirmation target.
+    pub fn fee_rate(mut self, sat_per_vb: f64) -> SendOptions {
+        self.fee_rate = Some(sat_per_vb);
+        self
+    }
+
+    /// Estimates a fee for confirmation within `blocks` blocks.
+    pub fn conf_target(mut self, blocks: u16) -> SendOptions {
+        self.conf_target = Some(blocks);
+        self
+    }
+
+    pub fn estimate_mode(mut self, mode: EstimateMode) -> SendOptions {
+        self.estimate_mode = Some(mode);
+        self
+    }
+
+    /// Takes the fee out of the amount sent instead of adding it on top.
+    pub fn subtract_fee(mut self, subtract: bool) -> SendOptions {
+        self.subtract_fee = subtract;
+        self
+    }
+
+    pub fn add_inputs(mut self, add_inputs: bool) -> SendOptions {
+        self.add_inputs = Some(add_inputs);
+        self
+    }
+
+    pub fn change_address(mut self, address: Address) -> SendOptions {
+        self.change_address = Some(address);
+        self
+    }
+
+    pub fn change_position(mut self, position: u32) -> SendOptions {
+        self.change_position = Some(position);
+        self
+    }
+
+    pub fn include_watching(mut self, include_watching: bool) -> SendOptions {
+        self.include_watching = Some(include_watching);
+        self
+    }
+
+    pub fn locktime(mut self, locktime: u32) -> SendOptions {
+        self.locktime = Some(locktime);
+        self
+    }
+
+    pub fn replaceable(mut self, replaceable: bool) -> SendOptions {
+        self.replaceable = Some(replaceable);
+        self
+    }
+
+    // Arguments of `sendtoaddress`, in Core's positional order.
+    pub(crate) fn send_to_address_args(&self, address: &str, amount: Amount) -> Vec<Value> {
+        vec![
+            address.into(),
+            json!(amount.to_btc()),
+            Value::Null, // comment
+            Value::Null, // comment_to
+            self.subtract_fee.into(),
+            json!(self.replaceable),
+            json!(self.conf_target),
+            json!(self.estimate_mode.map(|m| m.core_name())),
+            Value::Null, // avoid_reuse: wallet default
+            json!(self.fee_rate),
+        ]
+    }
+
+    // Arguments of `send`: outputs, conf_target, estimate_mode, fee_rate and an options
+    // object holding only the settings that were given.
+    pub(crate) fn send_args(&self, recipients: &[(Address, Amount)]) -> Vec<Value> {
+        let mut options = self.funding_options(recipients.len());
+        if let Some(locktime) = self.locktime {
+            options.insert("locktime".into(), locktime.into());
+        }
+        vec![
+            outputs(recipients),
+            json!(self.conf_target),
+            json!(self.estimate_mode.map(|m| m.core_name())),
+            json!(self.fee_rate),
+            Value::Object(options),
+        ]
+    }
+
+    // Arguments of `walletcreatefundedpsbt`: no preselected inputs, outputs, locktime,
+    // options (fee settings included) and BIP 32 derivation paths for signers.
+    pub(crate) fn funded_psbt_args(&self, recipients: &[(Address, Amount)]) -> Vec<Value> {
+        let mut options = self.funding_options(recipients.len());
+        let fee = [
+            ("conf_target", json!(self.conf_target)),
+            (
+                "estimate_mode",
+                json!(self.estimate_mode.map(|m| m.core_name())),
+            ),
+            ("fee_rate", json!(self.fee_rate)),
+        ];
+        insert_given(&mut options, fee);
+        vec![
+            json!([]),
+            outputs(recipients),
+            json!(self.locktime.unwrap_or(0)),
+            Value::Object(options),
+            true.into(),
+        ]
+    }
+
+    // Options shared by `send` and `walletcreatefundedpsbt`.
+    fn funding_options(&self, recipients: usize) -> Map<String, Value> {
+        let mut options = Map::new();
+        if self.subtract_fee {
+            let all: Vec<usize> = (0..recipients).collect();
+            options.insert("subtract_fee_from_outputs".into(), json!(all));
+        }
+        let optional = [
+            ("add_inputs", json!(self.add_inputs)),
+            (
+                "change_address",
+                json!(self.change_address.as_ref().map(Address::to_string)),
+            ),
+            ("change_position", json!(self.change_position)),
+            ("include_watching", json!(self.include_watching)),
+            ("replaceable", json!(self.replaceable)),
+        ];
+        insert_given(&mut options, optional);
+        options
+    }
+}
+
+fn outputs(recipients: &[(Address, Amount)]) -> Value {
+    recipients
+        .iter()
+        .map(|(address, amount)| json!({ address.to_string(): amount.to_btc() }))
+        .collect()
+}
+
+fn insert_given<const N: usize>(options: &mut Map<String, Value>, values: [(&str, Value); N]) {
+    for (key, value) in values {
+        if !value.is_null() {
+            options.insert(key.into(), value);
+        }
+    }
+}
+
+/// What `send` did with a payment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendResult {
+    /// Fully signed and broadcast.
+    Complete(Txid),
+    /// The wallet could not sign every input (e.g. watch-only or multisig inputs).
+    /// Nothing was broadcast; the base64 PSBT needs more signatures first.
+    Incomplete(String),
+}
+
+/// A broadcast payment and the fee it ended up paying.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sent {
+    pub txid: Txid,
+    pub fee: Amount,
+    /// Virtual size in vbytes.
+    pub vsize: u64,
+}
+
+impl Sent {
+    /// Effective fee rate in sat/vB.
+    pub fn fee_rate(&self) -> f64 {
+        self.fee.to_sat() as f64 / self.vsize as f64
+    }
+}
+
+impl fmt::Display for Sent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (fee {}, {} vB, {:.2} sat/vB)",
+            self.txid,
+            self.fee,
+            self.vsize,
+            self.fee_rate()
+        )
+    }
+}
@@ -0,0 +1,667 @@
+use crate::auth::Credentials;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::passphrase::Passphrase;
+use crate::send::{SendOptions, SendResult, Sent};
+use bitcoincore_rpc::bitcoin::address::NetworkUnchecked;
+use bitcoincore_rpc::bitcoin::{Address, Amount, BlockHash, Network, SignedAmount, Txid};
+use bitcoincore_rpc::{jsonrpc, Client, RpcApi};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Label of the addresses block rewards are mined to.
+pub const MINING_REWARD_LABEL: &str = "Mining Reward";
+/// Label of the addresses payments are received on.
+pub const RECEIVED_LABEL: &str = "Received";
+
+// Core's RPC_WALLET_INVALID_LABEL_NAME, returned by `getaddressesbylabel` for unused labels.
+const INVALID_LABEL_NAME: i32 = -11;
+// Core's RPC_WALLET_ERROR, RPC_WALLET_NOT_FOUND, RPC_WALLET_ALREADY_LOADED and
+// RPC_WALLET_ALREADY_EXISTS. `listdescriptors true` also fails with
+// RPC_WALLET_ERROR for wallets without private keys.
+pub(crate) const WALLET_ERROR: i32 = -4;
+const WALLET_NOT_FOUND: i32 = -18;
+const WALLET_ALREADY_LOADED: i32 = -35;
+const WALLET_ALREADY_EXISTS: i32 = -36;
+
+// `listdescriptors` entry; Core 27 added `next_index` and deprecated `next`.
+#[derive(Deserialize)]
+pub(crate) struct ListedDescriptor {
+    pub(crate) desc: String,
+    pub(crate) timestamp: u64,
+    pub(crate) active: bool,
+    #[serde(default)]
+    pub(crate) internal: bool,
+    pub(crate) range: Option<[u64; 2]>,
+    pub(crate) next: Option<u64>,
+    pub(crate) next_index: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct AddressInfo {
+    ismine: bool,
+    #[serde(default)]
+    ischange: bool,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+/// What a wallet knows about one of its own addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedAddress {
+    /// An internal (change) address.
+    pub change: bool,
+    /// The address label; `None` if it has none, as is usual for change.
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WalletInfo {
+    // Only present for encrypted wallets: 0 when locked
+    unlocked_until: Option<u64>,
+    private_keys_enabled: bool,
+    #[serde(default)]
+    descriptors: bool,
+    #[serde(default)]
+    avoid_reuse: bool,
+    // Reported since Core 26
+    blank: Option<bool>,
+}
+
+/// How a wallet is set up, built like `WalletSpec::new().disable_private_keys(true)`.
+/// The default is what a plain `createwallet` makes: a descriptor wallet with
+/// private keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletSpec {
+    /// A watch-only wallet, which can never hold private keys.
+    pub disable_private_keys: bool,
+    /// Created without keys or descriptors, to import them later.
+    pub blank: bool,
+    /// A descriptor wallet rather than a legacy one.
+    pub descriptors: bool,
+    /// Don't spend from addresses that were already spent from.
+    pub avoid_reuse: bool,
+    /// Add the wallet to (or remove it from) the node's startup list when it is
+    /// created or loaded; `None` leaves the list alone.
+    pub load_on_startup: Option<bool>,
+}
+
+impl Default for WalletSpec {
+    fn default() -> WalletSpec {
+        WalletSpec {
+            disable_private_keys: false,
+            blank: false,
+            descriptors: true,
+            avoid_reuse: false,
+            load_on_startup: None,
+        }
+    }
+}
+
+impl WalletSpec {
+    pub fn new() -> WalletSpec {
+        WalletSpec::default()
+    }
+
+    /// A wallet that monitors addresses or descriptors without private keys.
+    pub fn watch_only() -> WalletSpec {
+        WalletSpec::new().disable_private_keys(true)
+    }
+
+    pub fn disable_private_keys(mut self, disable: bool) -> WalletSpec {
+        self.disable_private_keys = disable;
+        self
+    }
+
+    pub fn blank(mut self, blank: bool) -> WalletSpec {
+        self.blank = blank;
+        self
+    }
+
+    /// Legacy wallets need a node started with `-deprecatedrpc=create_bdb`.
+    pub fn descriptors(mut self, descriptors: bool) -> WalletSpec {
+        self.descriptors = descriptors;
+        self
+    }
+
+    pub fn avoid_reuse(mut self, avoid_reuse: bool) -> WalletSpec {
+        self.avoid_reuse = avoid_reuse;
+        self
+    }
+
+    pub fn load_on_startup(mut self, load_on_startup: bool) -> WalletSpec {
+        self.load_on_startup = Some(load_on_startup);
+        self
+    }
+
+    // Settings in which `info` differs from this spec. `load_on_startup` isn't
+    // reported by the node, so it can't be checked.
+    fn mismatches(&self, info: &WalletInfo) -> Vec<SpecMismatch> {
+        let mut mismatches = Vec::new();
+        let mut check = |setting, expected, actual| {
+            if expected != actual {
+                mismatches.push(SpecMismatch {
+                    setting,
+                    expected,
+                    actual,
+                });
+            }
+        };
+        check(
+            "disable_private_keys",
+            self.disable_private_keys,
+            !info.private_keys_enabled,
+        );
+        check("descriptors", self.descriptors, info.descriptors);
+        check("avoid_reuse", self.avoid_reuse, info.avoid_reuse);
+        // Core clears the blank flag once keys are imported, so a wallet created
+        // blank may no longer be. One still blank must have been asked to be,
+        // else it has no keys to receive on.
+        if info.blank == Some(true) {
+            check("blank", self.blank, true);
+        }
+        mismatches
+    }
+}
+
+/// A setting of an existing wallet that differs from the requested [`WalletSpec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecMismatch {
+    /// The [`WalletSpec`] field.
+    pub setting: &'static str,
+    pub expected: bool,
+    pub actual: bool,
+}
+
+impl fmt::Display for SpecMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is {} (expected {})",
+            self.setting, self.actual, self.expected
+        )
+    }
+}
+
+/// A loaded wallet on the node, with a client bound to its `/wallet/<name>` endpoint.
+pub struct WalletHandle {
+    name: String,
+    client: Client,
+    network: Network,
+}
+
+impl WalletHandle {
+    pub fn new(name: &str, client: Client, network: Network) -> WalletHandle {
+        WalletHandle {
+            name: name.to_owned(),
+            client,
+            network,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Client for wallet RPC calls not wrapped here.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Whether the wallet is encrypted with a passphrase (`getwalletinfo`).
+    pub fn is_encrypted(&self) -> Result<bool> {
+        let info: WalletInfo = self.client.call("getwalletinfo", &[])?;
+        Ok(info.unlocked_until.is_some())
+    }
+
+    /// Encrypts an unencrypted wallet with `passphrase` (`encryptwallet`). The
+    /// wallet is locked afterwards.
+    pub fn encrypt(&self, passphrase: &Passphrase) -> Result<()> {
+        self.client
+            .call::<serde_json::Value>("encryptwallet", &[passphrase.expose().into()])?;
+        Ok(())
+    }
+
+    /// Unlocks the encrypted wallet for at most `timeout` seconds
+    /// (`walletpassphrase`). It is locked again when the returned guard drops,
+    /// or by the node when the timeout runs out, whichever comes first.
+    pub fn unlock(&self, passphrase: &Passphrase, timeout: u64) -> Result<Unlocked<'_>> {
+        self.client.call::<serde_json::Value>(
+            "walletpassphrase",
+            &[passphrase.expose().into(), timeout.into()],
+        )?;
+        Ok(Unlocked { wallet: self })
+    }
+
+    /// The wallet's descriptors (`listdescriptors`), with private keys if
+    /// `private`. `None` if private keys were asked of a wallet without them.
+    pub(crate) fn descriptors(&self, private: bool) -> Result<Option<Vec<ListedDescriptor>>> {
+        #[derive(Deserialize)]
+        struct Listed {
+            descriptors: Vec<ListedDescriptor>,
+        }
+        let params = if private { vec![true.into()] } else { vec![] };
+        match self.client.call::<Listed>("listdescriptors", &params) {
+            Ok(listed) => Ok(Some(listed.descriptors)),
+            Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e)))
+                if private && e.code == WALLET_ERROR =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Imports descriptors (`importdescriptors` requests), failing with
+    /// [`WalletError::ImportFailed`] if the wallet rejects any of them.
+    pub(crate) fn import_descriptors(&self, requests: Vec<Value>) -> Result<()> {
+        #[derive(Deserialize)]
+        struct Imported {
+            success: bool,
+            error: Option<ImportError>,
+        }
+        #[derive(Deserialize)]
+        struct ImportError {
+            message: String,
+        }
+        let imported: Vec<Imported> = self.client.call("importdescriptors", &[requests.into()])?;
+        if let Some(failed) = imported.into_iter().find(|i| !i.success) {
+            return Err(WalletError::ImportFailed {
+                wallet: self.name.clone(),
+                message: failed
+                    .error
+                    .map_or_else(|| "unknown error".to_owned(), |e| e.message),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Removes the wallet's keys from memory (`walletlock`).
+    pub fn lock(&self) -> Result<()> {
+        self.client.call::<serde_json::Value>("walletlock", &[])?;
+        Ok(())
+    }
+
+    /// Generates a new receiving address, checked against the node's network.
+    pub fn new_address(&self) -> Result<Address> {
+        let address = self.client.get_new_address(None, None)?;
+        Ok(address.require_network(self.network)?)
+    }
+
+    /// Generates a new receiving address with `label`.
+    pub fn new_labeled_address(&self, label: &str) -> Result<Address> {
+        let address = self.client.get_new_address(Some(label), None)?;
+        Ok(address.require_network(self.network)?)
+    }
+
+    /// This wallet's view of `address`, or `None` if the address isn't its own.
+    pub fn lookup(&self, address: &Address) -> Result<Option<OwnedAddress>> {
+        // The typed `get_address_info` result lacks `ischange`, so read just the fields we need.
+        let info: AddressInfo = self
+            .client
+            .call("getaddressinfo", &[address.to_string().into()])?;
+        if !info.ismine {
+            return Ok(None);
+        }
+        Ok(Some(OwnedAddress {
+            change: info.ischange,
+            label: info.labels.into_iter().find(|l| !l.is_empty()),
+        }))
+    }
+
+    /// Whether `address` belongs to this wallet.
+    pub fn is_mine(&self, address: &Address) -> Result<bool> {
+        Ok(self.lookup(address)?.is_some())
+    }
+
+    /// Whether `address` is one of this wallet's change (internal) addresses.
+    pub fn is_change(&self, address: &Address) -> Result<bool> {
+        Ok(self.lookup(address)?.is_some_and(|owned| owned.change))
+    }
+
+    /// Addresses carrying `label`; empty if the label is unused.
+    pub fn addresses_by_label(&self, label: &str) -> Result<Vec<Address>> {
+        let addresses: HashMap<Address<NetworkUnchecked>, serde_json::Value> =
+            match self.client.call("getaddressesbylabel", &[label.into()]) {
+                Ok(addresses) => addresses,
+                Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e)))
+                    if e.code == INVALID_LABEL_NAME =>
+                {
+                    return Ok(Vec::new());
+                }
+                Err(e) => return Err(Error::Rpc(e)),
+            };
+        let mut addresses = addresses
+            .into_keys()
+            .map(|a| a.require_network(self.network))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        addresses.sort_by_key(|a| a.to_string());
+        Ok(addresses)
+    }
+
+    /// Every label in use by this wallet (`listlabels`).
+    pub fn labels(&self) -> Result<Vec<String>> {
+        Ok(self.client.call("listlabels", &[])?)
+    }
+
+    /// Mines `blocks` blocks paying the coinbase to `address`.
+    pub fn mine(&self, blocks: u64, address: &Address) -> Result<Vec<BlockHash>> {
+        Ok(self.client.generate_to_address(blocks, address)?)
+    }
+
+    /// Pays `amount` to `address`, letting the wallet pick inputs. The fee follows `options`.
+    pub fn send_to_address(
+        &self,
+        address: &Address,
+        amount: Amount,
+        options: &SendOptions,
+    ) -> Result<Sent> {
+        // The typed `send_to_address` has no `fee_rate` argument
+        let args = options.send_to_address_args(&address.to_string(), amount);
+        let txid = self.client.call("sendtoaddress", &args)?;
+        self.sent(txid)
+    }
+
+    /// Pays each `(address, amount)` in one transaction, see [`send`].
+    pub fn send(
+        &self,
+        recipients: &[(Address, Amount)],
+        options: &SendOptions,
+    ) -> Result<SendResult> {
+        send(&self.client, recipients, options)
+    }
+
+    /// Fee and size of a transaction this wallet sent.
+    pub fn sent(&self, txid: Txid) -> Result<Sent> {
+        let details = self.client.get_transaction(&txid, None)?;
+        // `gettransaction` reports the fee as a negative amount
+        let fee = details
+            .fee
+            .unwrap_or(SignedAmount::ZERO)
+            .to_sat()
+            .unsigned_abs();
+        Ok(Sent {
+            txid,
+            fee: Amount::from_sat(fee),
+            vsize: details.transaction()?.vsize() as u64,
+        })
+    }
+}
+
+/// An unlocked encrypted wallet, locked again on drop. See [`WalletHandle::unlock`].
+pub struct Unlocked<'a> {
+    wallet: &'a WalletHandle,
+}
+
+impl Drop for Unlocked<'_> {
+    fn drop(&mut self) {
+        // Nothing to report a failure to here; the unlock timeout still bounds it
+        let _ = self.wallet.lock();
+    }
+}
+
+/// Pays each `(address, amount)` in one transaction. An incomplete send returns
+/// its PSBT instead of failing.
+pub fn send(
+    rpc: &Client,
+    recipients: &[(Address, Amount)],
+    options: &SendOptions,
+) -> Result<SendResult> {
+    let args = options.send_args(recipients);
+
+    #[derive(Deserialize)]
+    struct RawSendResult {
+        complete: bool,
+        txid: Option<Txid>,
+        psbt: Option<String>,
+    }
+    let send_result = rpc.call::<RawSendResult>("send", &args)?;
+    match send_result {
+        RawSendResult {
+            complete: true,
+            txid: Some(txid),
+            ..
+        } => Ok(SendResult::Complete(txid)),
+        RawSendResult {
+            complete: false,
+            psbt: Some(psbt),
+            ..
+        } => Ok(SendResult::Incomplete(psbt)),
+        _ => Err(Error::Rpc(bitcoincore_rpc::Error::UnexpectedStructure)),
+    }
+}
+
+/// Creates `wallet_name` as `spec` describes (`createwallet`), encrypted with
+/// `passphrase` if given.
+pub fn create_wallet(
+    rpc: &Client,
+    wallet_name: &str,
+    spec: &WalletSpec,
+    passphrase: Option<&Passphrase>,
+) -> Result<()> {
+    // The typed `create_wallet` has no `descriptors` or `load_on_startup` argument
+    let args = [
+        wallet_name.into(),
+        spec.disable_private_keys.into(),
+        spec.blank.into(),
+        passphrase.map(Passphrase::expose).into(),
+        spec.avoid_reuse.into(),
+        spec.descriptors.into(),
+        spec.load_on_startup.into(),
+    ];
+    rpc.call::<Value>("createwallet", &args)?;
+    Ok(())
+}
+
+/// Creates and/or loads `wallet_name` as needed and returns a client bound to it.
+/// A wallet that already existed must match `spec`, else this fails with
+/// [`Error::WalletMismatch`].
+pub fn ensure_wallet(
+    rpc: &Client,
+    config: &Config,
+    credentials: &Credentials,
+    wallet_name: &str,
+    spec: &WalletSpec,
+) -> Result<Client> {
+    let (client, outcome) = reconcile(rpc, config, credentials, wallet_name, Some(spec), None)?;
+    outcome.matched(wallet_name)?;
+    Ok(client)
+}
+
+/// What [`reconcile`] found on the node and did about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reconciled {
+    /// The wallet didn't exist; it was created and loaded.
+    Created,
+    /// It was in the wallet directory and got loaded.
+    Loaded,
+    /// It was loaded already, possibly by another client in the meantime.
+    AlreadyLoaded,
+    /// It is loaded, but set up differently from the requested [`WalletSpec`].
+    Mismatch(Vec<SpecMismatch>),
+}
+
+impl Reconciled {
+    /// The outcome, or [`Error::WalletMismatch`] for a mismatch.
+    pub fn matched(self, wallet_name: &str) -> Result<Reconciled> {
+        match self {
+            Reconciled::Mismatch(mismatches) => Err(Error::WalletMismatch {
+                wallet: wallet_name.to_owned(),
+                mismatches,
+            }),
+            outcome => Ok(outcome),
+        }
+    }
+}
+
+impl fmt::Display for Reconciled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reconciled::Created => f.write_str("created"),
+            Reconciled::Loaded => f.write_str("loaded"),
+            Reconciled::AlreadyLoaded => f.write_str("already loaded"),
+            Reconciled::Mismatch(mismatches) => {
+                let mismatches: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+                write!(f, "loaded, but {}", mismatches.join(", "))
+            }
+        }
+    }
+}
+
+/// Why [`reconcile`] could not get a wallet loaded. Each carries Core's message.
+#[derive(Debug)]
+pub enum WalletError {
+    /// `loadwallet` found no wallet at the path (`RPC_WALLET_NOT_FOUND`), e.g. a
+    /// directory in the wallet dir that holds no wallet.
+    NotFound { wallet: String, message: String },
+    /// The node has a wallet open under another name, likely these same files
+    /// loaded by path, so loading them again failed.
+    LoadedElsewhere {
+        wallet: String,
+        loaded_as: String,
+        message: String,
+    },
+    /// Core found the wallet but could not open it (`RPC_WALLET_ERROR`): corrupt
+    /// files, a newer wallet version, or a database locked by another node.
+    Unloadable { wallet: String, message: String },
+    /// `importdescriptors` rejected a descriptor.
+    ImportFailed { wallet: String, message: String },
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalletError::NotFound { wallet, message } => {
+                write!(f, "no loadable wallet {wallet}: {message}")
+            }
+            WalletError::LoadedElsewhere {
+                wallet,
+                loaded_as,
+                message,
+            } => write!(
+                f,
+                "cannot load wallet {wallet}: {message} (the node has it loaded as {loaded_as:?}; unload it or use that name)"
+            ),
+            WalletError::Unloadable { wallet, message } => {
+                write!(f, "cannot load wallet {wallet}: {message}")
+            }
+            WalletError::ImportFailed { wallet, message } => {
+                write!(f, "wallet {wallet} rejected a descriptor: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+/// Makes sure `wallet_name` is loaded on the node and returns a client bound
+/// to it with what was done. A missing wallet is created as `spec` describes
+/// (with the default spec when `None`), encrypted with the passphrase that
+/// `passphrase` reads if given. It is only called when a wallet with private
+/// keys is created, since Core rejects a passphrase for watch-only wallets.
+/// An existing wallet is loaded if needed and, given a spec, compared with it;
+/// a difference is reported as [`Reconciled::Mismatch`], not an error.
+///
+/// Running it again changes nothing. Races with other clients are absorbed: a
+/// `createwallet` that finds the wallet already there loads it instead, and a
+/// `loadwallet` that finds it loaded counts as [`Reconciled::AlreadyLoaded`].
+/// Other wallet errors from Core become a [`WalletError`].
+pub fn reconcile(
+    rpc: &Client,
+    config: &Config,
+    credentials: &Credentials,
+    wallet_name: &str,
+    spec: Option<&WalletSpec>,
+    passphrase: Option<&dyn Fn() -> Result<Rc<Passphrase>>>,
+) -> Result<(Client, Reconciled)> {
+    // Wallets loaded from outside the wallet directory are only listed here
+    let outcome = if rpc.list_wallets()?.iter().any(|w| w == wallet_name) {
+        Reconciled::AlreadyLoaded
+    } else if rpc.list_wallet_dir()?.iter().any(|w| w == wallet_name) {
+        load_wallet(rpc, wallet_name, spec)?
+    } else {
+        let default = WalletSpec::default();
+        let create_spec = spec.unwrap_or(&default);
+        let passphrase = match passphrase {
+            Some(read) if !create_spec.disable_private_keys => Some(read()?),
+            _ => None,
+        };
+        match create_wallet(rpc, wallet_name, create_spec, passphrase.as_deref()) {
+            Ok(()) => Reconciled::Created,
+            Err(Error::Rpc(e)) if already_exists(&e) => load_wallet(rpc, wallet_name, spec)?,
+            Err(e) => return Err(e),
+        }
+    };
+
+    // Return a new client bound to the loaded wallet
+    let wallet_client = credentials.connect(&config.wallet_url(wallet_name))?;
+
+    // A wallet we didn't just create may have been set up differently
+    let outcome = match spec {
+        Some(spec) if outcome != Reconciled::Created => {
+            let info: WalletInfo = wallet_client.call("getwalletinfo", &[])?;
+            let mismatches = spec.mismatches(&info);
+            if mismatches.is_empty() {
+                outcome
+            } else {
+                Reconciled::Mismatch(mismatches)
+            }
+        }
+        _ => outcome,
+    };
+    Ok((wallet_client, outcome))
+}
+
+// Loads `wallet_name`, taking a wallet another client loaded first as already loaded.
+fn load_wallet(rpc: &Client, wallet_name: &str, spec: Option<&WalletSpec>) -> Result<Reconciled> {
+    let mut args = vec![wallet_name.into()];
+    args.extend(spec.and_then(|s| s.load_on_startup).map(Value::from));
+    let e = match rpc.call::<Value>("loadwallet", &args) {
+        Ok(_) => return Ok(Reconciled::Loaded),
+        Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e))) => e,
+        Err(e) => return Err(e.into()),
+    };
+    let wallet = wallet_name.to_owned();
+    let message = e.message.clone();
+    match e.code {
+        WALLET_ALREADY_LOADED => Ok(Reconciled::AlreadyLoaded),
+        WALLET_NOT_FOUND => Err(WalletError::NotFound { wallet, message }.into()),
+        WALLET_ERROR => {
+            // Opening files the node already has open fails on the database lock
+            let loaded_as = rpc.list_wallets()?.into_iter().find(|w| {
+                w != wallet_name && Path::new(w).file_name() == Some(wallet_name.as_ref())
+            });
+            Err(match loaded_as {
+                Some(loaded_as) => WalletError::LoadedElsewhere {
+                    wallet,
+                    loaded_as,
+                    message,
+                },
+                None => WalletError::Unloadable { wallet, message },
+            }
+            .into())
+        }
+        _ => Err(Error::Rpc(jsonrpc::Error::Rpc(e).into())),
+    }
+}
+
+// Whether `createwallet` failed because the wallet is already there. Core before
+// 25 reports it as a generic wallet error.
+fn already_exists(e: &bitcoincore_rpc::Error) -> bool {
+    match e {
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e)) => {
+            e.code == WALLET_ALREADY_EXISTS
+                || (e.code == WALLET_ERROR && e.message.contains("already exists"))
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,51 @@
+use crate::error::Result;
+use crate::node::Node;
+use crate::report::TransactionReport;
+use crate::send::SendOptions;
+use crate::{mempool, mining, wallet};
+use bitcoincore_rpc::bitcoin::Amount;
+
+/// Runs the capstone flow: fund the Miner wallet, pay the Trader 20 BTC,
+/// confirm it and return the payment's report.
+pub fn run(node: &Node) -> Result<TransactionReport> {
+    let config = node.config();
+
+    // Create/Load the wallets, named 'Miner' and 'Trader'. Have logic to optionally create/load them if they do not exist or not loaded already.
+    // Ensure 'Miner' wallet is created/loaded
+    let miner_wallet = node.ensure_wallet(&config.miner_wallet)?;
+
+    // Ensure 'Trader' wallet is created/loaded
+    let trader_wallet = node.ensure_wallet(&config.trader_wallet)?;
+
+    // Generate spendable balances in the Miner wallet. How many blocks needs to be mined?
+    let mining_address = miner_wallet.new_labeled_address(wallet::MINING_REWARD_LABEL)?;
+
+    // Mine until the wallet has a spendable balance. A block reward only becomes spendable
+    // after COINBASE_MATURITY (100) confirmations, so on a fresh chain the first reward
+    // matures after 101 blocks. Until then `getbalances` reports it as immature rather than
+    // trusted, and the rewards of the 100 later blocks stay immature after that.
+    let maturity = mining::mine_until_spendable(&miner_wallet, &mining_address, |_| {})?;
+    println!("{}", maturity.explanation());
+    println!("Miner balance: {}", maturity.trusted);
+
+    // Send 20 BTC from Miner to Trader
+    let trader_address = trader_wallet.new_labeled_address(wallet::RECEIVED_LABEL)?;
+    let amount = Amount::from_btc(20.0)?;
+    // No fee settings: the node estimates one, falling back to `fallbackfee` on a fresh chain
+    let unlocked = node.unlock(&miner_wallet)?;
+    let sent = miner_wallet.send_to_address(&trader_address, amount, &SendOptions::new())?;
+    drop(unlocked);
+    println!("Sent {amount}: {sent}");
+    let txid = sent.txid;
+
+    // Fetch the unconfirmed transaction from the node's mempool
+    let entry = mempool::entry(node.rpc(), &txid)?;
+    println!("Mempool entry for {txid}:\n{entry}");
+
+    // Mine 1 block to confirm the transaction
+    miner_wallet.mine(1, &mining_address)?;
+
+    // Extract all required transaction details
+    let report = TransactionReport::extract(node, &miner_wallet, &txid)?;
+    Ok(report)
+}
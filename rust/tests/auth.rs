@@ -0,0 +1,48 @@
+//! Offline tests of credential resolution against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use capstone::auth::{AuthMethod, Credentials};
+use capstone::{Config, Node};
+use mock_rpc::MockRpc;
+use std::{env, fs, process};
+
+#[test]
+fn unreadable_cookie_is_returned_not_printed() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo");
+    let datadir = env::temp_dir().join(format!("capstone-datadir-{}", process::id()));
+    let cookie = datadir.join("regtest").join(".cookie");
+    fs::create_dir_all(cookie.parent().unwrap()).unwrap();
+    // bitcoind writes `__cookie__:<password>`; without the colon it can't be used
+    fs::write(&cookie, "garbage").unwrap();
+    let config = Config {
+        rpc_user: None,
+        rpc_pass: None,
+        datadir: Some(datadir.clone()),
+        ..mock.config()
+    };
+
+    let credentials = Credentials::resolve(&config).unwrap();
+    let node = Node::connect(config);
+    fs::remove_dir_all(&datadir).unwrap();
+
+    assert_eq!(
+        credentials.method,
+        AuthMethod::UserPass {
+            user: "alice".to_owned(),
+            explicit: false,
+        }
+    );
+    let skipped = credentials.skipped_cookie.unwrap();
+    assert_eq!(skipped.path, cookie);
+    assert!(
+        skipped
+            .to_string()
+            .starts_with(&format!("ignored cookie file {}: ", cookie.display())),
+        "{skipped}"
+    );
+    // The built-in user/password still gets through
+    let node = node.unwrap();
+    assert_eq!(node.credentials().skipped_cookie, Some(skipped));
+}
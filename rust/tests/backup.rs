@@ -0,0 +1,277 @@
+//! Offline tests of wallet backup and restore against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::Amount;
+use capstone::backup::{self, Backup, BackupError};
+use capstone::passphrase::PassphraseSource;
+use capstone::wallet::WalletError;
+use capstone::{Config, Error, Node};
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::{json, Value};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::{env, fs, io, process};
+
+// Core's RPC_WALLET_ERROR.
+const WALLET_ERROR: i32 = -4;
+
+const SECRET: &str = "correct horse battery staple";
+
+// Replies describing the Trader wallet after it received 20 BTC.
+fn mock_trader() -> MockRpc {
+    let mock = MockRpc::start();
+    mock.reply("listdescriptors", "listdescriptors_private")
+        .reply("listreceivedbyaddress", "listreceivedbyaddress")
+        .reply("getbalances", "getbalances")
+        .reply("listwallets", "listwallets")
+        .reply("listwalletdir", "listwalletdir");
+    mock
+}
+
+fn trader_backup(mock: &MockRpc) -> Backup {
+    let node = mock.node();
+    backup::backup(&node.loaded_wallet("Trader").unwrap()).unwrap()
+}
+
+#[test]
+fn backup_records_descriptors_labels_and_balances() {
+    let mock = mock_trader();
+
+    let backup = trader_backup(&mock);
+
+    assert_eq!(backup.format, backup::FORMAT);
+    assert_eq!(backup.wallet, "Trader");
+    assert!(backup.private_keys);
+    let listed = &load_fixture("listdescriptors_private")["descriptors"];
+    assert_eq!(backup.descriptors.len(), 2);
+    assert_eq!(backup.descriptors[0].desc, listed[0]["desc"]);
+    assert!(backup.descriptors[1].internal);
+    assert_eq!(backup.descriptors[0].range, Some([0, 1000]));
+    assert_eq!(backup.descriptors[0].next_index, Some(2));
+    assert_eq!(
+        backup.labels["bcrt1qqyqszqgpqyqszqgpqyqszqgpqyqszqgpvxat9t"],
+        "Received"
+    );
+    assert_eq!(backup.labels.len(), 2);
+    assert_eq!(backup.balances.trusted, Amount::from_btc(20.0).unwrap());
+    assert_eq!(mock.calls()[1].params, json!([true]));
+
+    let path = env::temp_dir().join(format!("capstone-backup-{}.json", process::id()));
+    backup.save(&path).unwrap();
+    let loaded = Backup::load(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), backup);
+}
+
+#[test]
+fn backup_file_is_private_and_never_overwritten() {
+    let mock = mock_trader();
+    let backup = trader_backup(&mock);
+    let path = env::temp_dir().join(format!("capstone-backup-mode-{}.json", process::id()));
+
+    backup.save(&path).unwrap();
+    #[cfg(unix)]
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    let again = backup.save(&path);
+    fs::remove_file(&path).unwrap();
+
+    #[cfg(unix)]
+    assert_eq!(mode & 0o777, 0o600);
+    let e = again.unwrap_err();
+    assert!(
+        matches!(&e, Error::Io(e) if e.kind() == io::ErrorKind::AlreadyExists),
+        "{e:?}"
+    );
+}
+
+#[test]
+fn backup_of_watch_only_wallet_keeps_public_descriptors() {
+    let mock = mock_trader();
+    mock.fail(
+        "listdescriptors",
+        WALLET_ERROR,
+        "Can't get descriptor string.",
+    )
+    .reply_for("listdescriptors", Value::Null, "listdescriptors_trader");
+
+    let backup = trader_backup(&mock);
+
+    assert!(!backup.private_keys);
+    assert!(backup.descriptors.iter().all(|d| d.desc.contains("tpub")));
+}
+
+#[test]
+fn restore_imports_labels_and_verifies() {
+    let mock = mock_trader();
+    let backup = trader_backup(&mock);
+    mock.reply("createwallet", "createwallet")
+        .reply("getwalletinfo", "getwalletinfo")
+        .reply("importdescriptors", "importdescriptors")
+        .reply_value("setlabel", Value::Null);
+    let node = mock.node();
+
+    let (wallet, verification) = backup::restore(&node, &backup, "Restored", Some(0)).unwrap();
+
+    assert_eq!(wallet.name(), "Restored");
+    assert!(verification.matches(), "{verification}");
+    let calls = mock.calls();
+    let create = calls.iter().find(|c| c.method == "createwallet").unwrap();
+    assert_eq!(
+        create.params,
+        json!(["Restored", false, true, null, false, true, null])
+    );
+    let import = calls
+        .iter()
+        .find(|c| c.method == "importdescriptors")
+        .unwrap();
+    assert_eq!(import.path, "/wallet/Restored");
+    assert_eq!(
+        import.params[0][0],
+        json!({
+            "desc": backup.descriptors[0].desc,
+            "timestamp": 0,
+            "active": true,
+            "internal": false,
+            "range": [0, 1000],
+            "next_index": 2
+        })
+    );
+    let labelled: Vec<&Value> = calls
+        .iter()
+        .filter(|c| c.method == "setlabel")
+        .map(|c| &c.params)
+        .collect();
+    assert_eq!(
+        labelled[0],
+        &json!(["bcrt1qqszqgpqyqszqgpqyqszqgpqyqszqgpqyuza2rq", ""])
+    );
+    assert_eq!(labelled.len(), 2);
+}
+
+#[test]
+fn restore_encrypts_wallet_with_the_passphrase() {
+    let mock = mock_trader();
+    let backup = trader_backup(&mock);
+    mock.reply("createwallet", "createwallet")
+        .reply("getwalletinfo", "getwalletinfo_encrypted")
+        .reply("walletpassphrase", "walletpassphrase")
+        .reply("walletlock", "walletlock")
+        .reply("importdescriptors", "importdescriptors")
+        .reply_value("setlabel", Value::Null);
+    let var = format!("CAPSTONE_TEST_RESTORE_{}", process::id());
+    env::set_var(&var, SECRET);
+    let node = Node::connect(Config {
+        passphrase_from: Some(PassphraseSource::Env(var.clone())),
+        ..mock.config()
+    })
+    .unwrap();
+
+    let restored = backup::restore(&node, &backup, "Restored", None);
+    env::remove_var(&var);
+    restored.unwrap();
+
+    let calls = mock.calls();
+    let create = calls.iter().find(|c| c.method == "createwallet").unwrap();
+    assert_eq!(create.params[3], SECRET);
+    // Private descriptors are imported while the wallet is unlocked
+    let methods: Vec<&str> = calls
+        .iter()
+        .filter(|c| c.path == "/wallet/Restored")
+        .map(|c| c.method.as_str())
+        .take(4)
+        .collect();
+    assert_eq!(
+        methods,
+        [
+            "getwalletinfo",
+            "walletpassphrase",
+            "importdescriptors",
+            "walletlock"
+        ]
+    );
+}
+
+#[test]
+fn restore_reports_mismatched_balances() {
+    let mock = mock_trader();
+    let backup = trader_backup(&mock);
+    mock.reply("createwallet", "createwallet")
+        .reply("getwalletinfo", "getwalletinfo")
+        .reply("importdescriptors", "importdescriptors")
+        .reply_value("setlabel", Value::Null)
+        .reply("getbalances", "getbalances_empty");
+    let node = mock.node();
+
+    let e = backup::restore(&node, &backup, "Restored", None)
+        .err()
+        .unwrap();
+
+    match &e {
+        Error::Backup(BackupError::Mismatch {
+            wallet,
+            verification,
+        }) => {
+            assert_eq!(wallet, "Restored");
+            assert_eq!(verification.actual.trusted, Amount::ZERO);
+            assert!(verification.missing.is_empty());
+        }
+        e => panic!("expected a mismatch, got {e:?}"),
+    }
+    assert_eq!(e.exit_code(), 6);
+}
+
+#[test]
+fn restore_imports_again_into_a_wallet_left_by_a_failed_restore() {
+    let mock = mock_trader();
+    let backup = trader_backup(&mock);
+    mock.reply("createwallet", "createwallet")
+        .reply("getwalletinfo", "getwalletinfo")
+        .reply_value(
+            "importdescriptors",
+            json!([{ "success": false, "error": { "code": -1, "message": "Rescan failed" } }]),
+        )
+        .reply_value("setlabel", Value::Null);
+    let node = mock.node();
+
+    let e = backup::restore(&node, &backup, "Restored", None)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(&e, Error::Wallet(WalletError::ImportFailed { wallet, message })
+            if wallet == "Restored" && message == "Rescan failed"),
+        "{e:?}"
+    );
+
+    // The wallet was created and stays loaded; the second run imports into it
+    mock.reply_value("listwallets", json!(["Miner", "Trader", "Restored"]))
+        .reply("importdescriptors", "importdescriptors");
+    let (_, verification) = backup::restore(&node, &backup, "Restored", None).unwrap();
+
+    assert!(verification.matches(), "{verification}");
+    let methods = mock.methods();
+    let count = |method: &str| methods.iter().filter(|m| *m == method).count();
+    assert_eq!(count("createwallet"), 1, "{methods:?}");
+    assert_eq!(count("importdescriptors"), 2, "{methods:?}");
+}
+
+#[test]
+fn load_rejects_unknown_version() {
+    let mock = mock_trader();
+    let mut backup = serde_json::to_value(trader_backup(&mock)).unwrap();
+    backup["version"] = 2.into();
+    let path = env::temp_dir().join(format!("capstone-backup-v2-{}.json", process::id()));
+    fs::write(&path, backup.to_string()).unwrap();
+
+    let e = Backup::load(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+
+    assert!(
+        matches!(
+            e,
+            Error::Backup(BackupError::Unsupported { version: 2, .. })
+        ),
+        "{e:?}"
+    );
+    assert_eq!(e.exit_code(), 2);
+}
@@ -0,0 +1,75 @@
+//! Offline tests of fee bumping against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::{Amount, Txid};
+use capstone::bump;
+use capstone::report::ReportFormat;
+use capstone::Error;
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::json;
+use std::str::FromStr;
+
+const TXID: &str = "6c2fae5e0e51422beb17fde022dca96279827a9f20852e8179f6d6dadaef9505";
+const REPLACEMENT: &str = "3b2a8c4f0e7d9a1b5c6e2f8a4d0b7c3e9f1a5d2b8c4e0f6a3d7b9c1e5f2a8d04";
+
+// Core's RPC_INVALID_PARAMETER.
+const INVALID_PARAMETER: i32 = -8;
+
+#[test]
+fn bump_fee_reports_both_txids_and_fee_delta() {
+    let mock = MockRpc::start();
+    mock.reply("bumpfee", "bumpfee");
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let txid = Txid::from_str(TXID).unwrap();
+
+    let bumped = bump::bump_fee(&miner, &txid, 50.0).unwrap();
+
+    assert_eq!(bumped.replaced, txid);
+    assert_eq!(bumped.replacement, Txid::from_str(REPLACEMENT).unwrap());
+    assert_eq!(bumped.original_fee, Amount::from_sat(1410));
+    assert_eq!(bumped.fee, Amount::from_sat(5650));
+    assert_eq!(bumped.fee_delta, Amount::from_sat(4240));
+    let call = mock.calls().pop().unwrap();
+    assert_eq!(call.path, "/wallet/Miner");
+    assert_eq!(call.params, json!([TXID, { "fee_rate": 50.0 }]));
+
+    let mut text = Vec::new();
+    bumped.write(ReportFormat::Text, &mut text).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        format!("{TXID}\n{REPLACEMENT}\n0.0000141\n0.0000565\n0.0000424\n")
+    );
+}
+
+#[test]
+fn psbt_bump_fee_returns_unsigned_replacement() {
+    let mock = MockRpc::start();
+    mock.reply("psbtbumpfee", "psbtbumpfee");
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+
+    let bumped = bump::psbt_bump_fee(&miner, &Txid::from_str(TXID).unwrap(), 50.0).unwrap();
+
+    assert_eq!(bumped.psbt, load_fixture("psbtbumpfee")["psbt"]);
+    assert_eq!(bumped.fee, Amount::from_sat(5650));
+    assert_eq!(mock.methods().last().unwrap(), "psbtbumpfee");
+}
+
+#[test]
+fn bump_fee_reports_rejected_replacement() {
+    let mock = MockRpc::start();
+    mock.fail(
+        "bumpfee",
+        INVALID_PARAMETER,
+        "Insufficient total fee 0.00001410, must be at least 0.00001523 (oldFee 0.00001410 + incrementalFee 0.00000113)",
+    );
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+
+    let e = bump::bump_fee(&miner, &Txid::from_str(TXID).unwrap(), 1.0).unwrap_err();
+
+    assert!(matches!(e, Error::Rpc(_)), "{e:?}");
+    assert_eq!(e.exit_code(), 5);
+}
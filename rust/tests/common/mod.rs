@@ -0,0 +1,144 @@
+//! A throwaway `bitcoind -regtest` for integration tests.
+
+use capstone::network::Chain;
+use capstone::report::ReportFormat;
+use capstone::{Config, Node};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fs, thread};
+
+const RPC_USER: &str = "alice";
+const RPC_PASS: &str = "password";
+
+// How long a fresh node gets to start answering RPC calls.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A regtest node in its own temporary datadir, listening for RPC on a free
+/// port. It is killed and its datadir removed on drop.
+pub struct Bitcoind {
+    child: Child,
+    datadir: PathBuf,
+    rpc_port: u16,
+}
+
+impl Bitcoind {
+    /// Starts a node and waits until it answers RPC calls. Panics when no
+    /// `bitcoind` binary is found (set `BITCOIND` or put it on `PATH`), so a
+    /// test that needs a node fails rather than passing without one.
+    pub fn spawn() -> Bitcoind {
+        let binary = find_bitcoind().expect("no bitcoind found (set BITCOIND or add it to PATH)");
+        let datadir = temp_datadir();
+        let rpc_port = free_port();
+
+        let child = Command::new(&binary)
+            .arg("-regtest")
+            .arg(format!("-datadir={}", datadir.display()))
+            .arg(format!("-rpcport={rpc_port}"))
+            .arg(format!("-rpcuser={RPC_USER}"))
+            .arg(format!("-rpcpassword={RPC_PASS}"))
+            // Same settings as ../bitcoin.conf, minus P2P: this node never needs peers
+            .args([
+                "-server=1",
+                "-listen=0",
+                "-txindex=1",
+                "-fallbackfee=0.00001",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|e| panic!("cannot start {}: {e}", binary.display()));
+
+        let mut bitcoind = Bitcoind {
+            child,
+            datadir,
+            rpc_port,
+        };
+        bitcoind.wait_ready();
+        bitcoind
+    }
+
+    /// Settings pointing the library at this node, with the default wallet names.
+    pub fn config(&self) -> Config {
+        Config {
+            rpc_host: "127.0.0.1".to_owned(),
+            rpc_port: self.rpc_port,
+            rpc_user: Some(RPC_USER.to_owned()),
+            rpc_pass: Some(RPC_PASS.to_owned()),
+            rpc_cookie: None,
+            datadir: Some(self.datadir.clone()),
+            network: Some(Chain::Regtest),
+            miner_wallet: "Miner".to_owned(),
+            trader_wallet: "Trader".to_owned(),
+            output: self.datadir.join("out.txt"),
+            format: ReportFormat::Text,
+            passphrase_from: None,
+            unlock_timeout: 60,
+        }
+    }
+
+    pub fn connect(&self) -> Node {
+        Node::connect(self.config()).expect("connect to regtest node")
+    }
+
+    // Polls until an RPC call succeeds. The node answers -28 (warming up) for
+    // a while after the port opens, so a refused connection is not the only
+    // error to retry on.
+    fn wait_ready(&mut self) {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            let error = match Node::connect(self.config()) {
+                Ok(_) => return,
+                Err(e) => e,
+            };
+            if let Some(status) = self.child.try_wait().expect("poll bitcoind") {
+                panic!("bitcoind exited with {status} during startup: {error}");
+            }
+            if Instant::now() >= deadline {
+                panic!("bitcoind not ready after {STARTUP_TIMEOUT:?}: {error}");
+            }
+            thread::sleep(STARTUP_POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for Bitcoind {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.datadir);
+    }
+}
+
+fn find_bitcoind() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("BITCOIND") {
+        let path = PathBuf::from(path);
+        assert!(path.is_file(), "BITCOIND={} is not a file", path.display());
+        return Some(path);
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join("bitcoind"))
+        .find(|path| path.is_file())
+}
+
+// A port the OS just handed out. Another process could grab it before
+// bitcoind binds it, but that is unlikely enough for tests.
+fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind an ephemeral port");
+    listener
+        .local_addr()
+        .expect("ephemeral port address")
+        .port()
+}
+
+fn temp_datadir() -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock after epoch")
+        .as_nanos();
+    let dir = env::temp_dir().join(format!("capstone-regtest-{}-{nanos}", std::process::id()));
+    fs::create_dir_all(&dir).expect("create temp datadir");
+    dir
+}
@@ -0,0 +1,169 @@
+//! Offline tests of child-pays-for-parent against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::consensus::encode::deserialize;
+use bitcoincore_rpc::bitcoin::hashes::hex::FromHex;
+use bitcoincore_rpc::bitcoin::{Amount, OutPoint, Transaction, Txid};
+use capstone::cpfp::{self, CpfpError};
+use capstone::{Error, Node};
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::json;
+use std::str::FromStr;
+
+const PARENT: &str = "6c2fae5e0e51422beb17fde022dca96279827a9f20852e8179f6d6dadaef9505";
+const CHILD: &str = "1097aa02f5fcafaccff8a8ceba3f6de76cf071f3b24b3b5596d925aa6a3dc8bd";
+
+// Replies for a Trader receiving 20 BTC in PARENT (113 vB, 1410 sat fee), with
+// the signed child being 110 vB.
+fn mock_trader(child_entry: &str) -> MockRpc {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply_for("getmempoolentry", PARENT, "getmempoolentry_parent")
+        .reply_for("getmempoolentry", CHILD, child_entry)
+        .reply("listunspent", "listunspent_unconfirmed")
+        .reply("getnewaddress", "getnewaddress")
+        .reply(
+            "signrawtransactionwithwallet",
+            "signrawtransactionwithwallet",
+        )
+        .reply("sendrawtransaction", "sendrawtransaction_child");
+    mock
+}
+
+fn parent() -> Txid {
+    Txid::from_str(PARENT).unwrap()
+}
+
+#[test]
+fn accelerate_pays_for_the_package() {
+    let mock = mock_trader("getmempoolentry_child");
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    let cpfp = cpfp::accelerate(&trader, &parent(), 20.0).unwrap();
+
+    // 20 sat/vB over 113 + 110 vB is 4460 sat, of which the parent pays 1410
+    let signed: Vec<Transaction> = mock
+        .calls()
+        .iter()
+        .filter(|c| c.method == "signrawtransactionwithwallet")
+        .map(|c| {
+            let hex = Vec::<u8>::from_hex(c.params[0].as_str().unwrap()).unwrap();
+            deserialize(&hex).unwrap()
+        })
+        .collect();
+    let child = signed.last().unwrap();
+    assert_eq!(child.input.len(), 1);
+    assert_eq!(child.input[0].previous_output, OutPoint::new(parent(), 0));
+    assert_eq!(
+        child.output[0].value,
+        Amount::from_btc(20.0).unwrap() - Amount::from_sat(3050)
+    );
+
+    assert_eq!(cpfp.child, Txid::from_str(CHILD).unwrap());
+    assert_eq!(cpfp.fee, Amount::from_sat(3050));
+    assert_eq!(cpfp.package_vsize, 223);
+    assert!((cpfp.package_fee_rate() - 20.0).abs() < 1e-9);
+}
+
+#[test]
+fn accelerate_rejects_package_below_target() {
+    let mock = mock_trader("getmempoolentry_child_short");
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    let e = cpfp::accelerate(&trader, &parent(), 20.0).unwrap_err();
+
+    assert!(
+        matches!(&e, Error::Cpfp(CpfpError::BelowTarget { target, broadcast: true, .. }) if *target == 20.0),
+        "{e:?}"
+    );
+    assert!(e.to_string().contains(CHILD), "{e}");
+    assert!(e.to_string().ends_with("already broadcast"), "{e}");
+    assert_eq!(e.exit_code(), 6);
+}
+
+#[test]
+fn accelerate_refuses_dust_child() {
+    let mock = mock_trader("getmempoolentry_child");
+    // 3300 sat, of which the child's fee of 3050 leaves 250, below 294 for P2WPKH
+    let mut unspent = load_fixture("listunspent_unconfirmed");
+    unspent[0]["amount"] = json!(0.000033);
+    mock.reply_value("listunspent", unspent);
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    let e = cpfp::accelerate(&trader, &parent(), 20.0).unwrap_err();
+
+    assert!(
+        matches!(
+            &e,
+            Error::Cpfp(CpfpError::Dust { value, dust_limit, .. })
+                if *value == Amount::from_sat(250) && *dust_limit == Amount::from_sat(294)
+        ),
+        "{e:?}"
+    );
+    assert!(!mock.methods().contains(&"sendrawtransaction".to_owned()));
+}
+
+#[test]
+fn accelerate_needs_an_output_of_the_parent() {
+    let mock = mock_trader("getmempoolentry_child");
+    mock.reply_value("listunspent", json!([]));
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    let e = cpfp::accelerate(&trader, &parent(), 20.0).unwrap_err();
+
+    assert!(
+        matches!(&e, Error::Cpfp(CpfpError::NothingToSpend { wallet, .. }) if wallet == "Trader"),
+        "{e:?}"
+    );
+    assert!(!mock.methods().contains(&"sendrawtransaction".to_owned()));
+}
+
+#[test]
+fn accelerate_reports_undecodable_signed_child() {
+    let mock = mock_trader("getmempoolentry_child");
+    mock.reply_value(
+        "signrawtransactionwithwallet",
+        json!({ "hex": "0200", "complete": true }),
+    );
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    let e = cpfp::accelerate(&trader, &parent(), 20.0).unwrap_err();
+
+    assert!(matches!(e, Error::Decode(_)), "{e:?}");
+    assert_eq!(e.exit_code(), 7);
+    assert!(!mock.methods().contains(&"sendrawtransaction".to_owned()));
+}
+
+#[test]
+fn accelerate_refuses_incompletely_signed_child() {
+    let mock = mock_trader("getmempoolentry_child");
+    let mut signed = load_fixture("signrawtransactionwithwallet");
+    signed["complete"] = false.into();
+    signed["errors"] = json!([{
+        "txid": PARENT,
+        "vout": 0,
+        "witness": [],
+        "scriptSig": "",
+        "sequence": 4294967293u32,
+        "error": "Unable to sign input, invalid stack size (possibly missing key)"
+    }]);
+    mock.reply_value("signrawtransactionwithwallet", signed);
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    let e = cpfp::accelerate(&trader, &parent(), 20.0).unwrap_err();
+
+    assert!(
+        matches!(&e, Error::Cpfp(CpfpError::Unsigned { wallet, reason, .. })
+            if wallet == "Trader" && reason.contains("missing key")),
+        "{e:?}"
+    );
+    assert_eq!(e.exit_code(), 6);
+    assert!(!mock.methods().contains(&"sendrawtransaction".to_owned()));
+}
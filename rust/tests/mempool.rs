@@ -0,0 +1,114 @@
+//! Offline tests of the mempool queries against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::{Amount, Txid};
+use capstone::mempool::{self, MempoolEntry};
+use capstone::Error;
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::json;
+use std::str::FromStr;
+
+const PARENT: &str = "6c2fae5e0e51422beb17fde022dca96279827a9f20852e8179f6d6dadaef9505";
+const CHILD: &str = "1097aa02f5fcafaccff8a8ceba3f6de76cf071f3b24b3b5596d925aa6a3dc8bd";
+
+// Core's RPC_INVALID_ADDRESS_OR_KEY, returned for transactions not in the mempool.
+const INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+fn txid(txid: &str) -> Txid {
+    Txid::from_str(txid).unwrap()
+}
+
+#[test]
+fn entry_decodes_sizes_fees_and_relatives() {
+    let mock = MockRpc::start();
+    mock.reply_for("getmempoolentry", CHILD, "getmempoolentry_child");
+    let node = mock.node();
+
+    let entry = mempool::entry(node.rpc(), &txid(CHILD)).unwrap();
+
+    assert_eq!(entry.vsize, 110);
+    assert_eq!(entry.weight, Some(438));
+    assert_eq!((entry.ancestor_count, entry.ancestor_size), (2, 223));
+    assert_eq!((entry.descendant_count, entry.descendant_size), (1, 110));
+    assert_eq!(entry.fees.base, Amount::from_sat(3050));
+    assert_eq!(entry.fees.ancestor, Amount::from_sat(4460));
+    assert_eq!(entry.depends, [txid(PARENT)]);
+    assert!(entry.spent_by.is_empty());
+    assert!(entry.bip125_replaceable);
+    assert_eq!(entry.unbroadcast, Some(true));
+    assert!((entry.fee_rate() - 3050.0 / 110.0).abs() < 1e-9);
+    assert!((entry.ancestor_fee_rate() - 20.0).abs() < 1e-9);
+    let shown = entry.to_string();
+    assert!(shown.contains("fee rate: 27.73 sat/vB"), "{shown}");
+    assert!(shown.contains(&format!("depends: {PARENT}")), "{shown}");
+    assert!(shown.contains("spent by: -"), "{shown}");
+}
+
+#[test]
+fn entry_defaults_fields_older_or_full_rbf_nodes_leave_out() {
+    let mut listed = load_fixture("getmempoolentry_parent");
+    let fields = listed.as_object_mut().unwrap();
+    for field in ["weight", "bip125-replaceable", "unbroadcast"] {
+        fields.remove(field);
+    }
+
+    let entry: MempoolEntry = serde_json::from_value(listed).unwrap();
+
+    assert_eq!(entry.weight, None);
+    assert!(!entry.bip125_replaceable);
+    assert_eq!(entry.unbroadcast, None);
+}
+
+#[test]
+fn entry_of_an_unknown_transaction_is_an_rpc_error() {
+    let mock = MockRpc::start();
+    mock.fail(
+        "getmempoolentry",
+        INVALID_ADDRESS_OR_KEY,
+        "Transaction not in mempool",
+    );
+    let node = mock.node();
+
+    let e = mempool::entry(node.rpc(), &txid(PARENT)).unwrap_err();
+
+    assert!(matches!(e, Error::Rpc(_)), "{e:?}");
+    assert_eq!(e.exit_code(), 5);
+}
+
+#[test]
+fn ancestors_descendants_and_all_are_keyed_by_txid() {
+    let mock = MockRpc::start();
+    let parent = load_fixture("getmempoolentry_parent");
+    let child = load_fixture("getmempoolentry_child");
+    mock.reply_value("getmempoolancestors", json!({ PARENT: parent }))
+        .reply_value("getmempooldescendants", json!({ CHILD: child }))
+        .reply_value("getrawmempool", json!({ PARENT: parent, CHILD: child }));
+    let node = mock.node();
+
+    let ancestors = mempool::ancestors(node.rpc(), &txid(CHILD)).unwrap();
+    let descendants = mempool::descendants(node.rpc(), &txid(PARENT)).unwrap();
+    let all = mempool::all(node.rpc()).unwrap();
+
+    assert_eq!(ancestors.len(), 1);
+    assert_eq!(ancestors[&txid(PARENT)].vsize, 113);
+    assert_eq!(descendants.len(), 1);
+    assert_eq!(descendants[&txid(CHILD)].depends, [txid(PARENT)]);
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[&txid(PARENT)], ancestors[&txid(PARENT)]);
+    // The verbose forms are asked for, so entries come back rather than txids
+    let params: Vec<_> = mock
+        .calls()
+        .into_iter()
+        .filter(|c| c.method != "getblockchaininfo")
+        .map(|c| (c.method, c.params))
+        .collect();
+    assert_eq!(
+        params,
+        [
+            ("getmempoolancestors".to_owned(), json!([CHILD, true])),
+            ("getmempooldescendants".to_owned(), json!([PARENT, true])),
+            ("getrawmempool".to_owned(), json!([true])),
+        ]
+    );
+}
@@ -0,0 +1,117 @@
+//! Offline tests of mining until a wallet can spend, against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::{Address, Amount, Network};
+use capstone::mining::{self, Maturity, COINBASE_MATURITY};
+use capstone::Error;
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::{json, Value};
+use std::cell::Cell;
+
+const MINER_ADDRESS: &str = "bcrt1qqszqgpqyqszqgpqyqszqgpqyqszqgpqyuza2rq";
+const BLOCK: &str = "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206";
+
+fn address() -> Address {
+    MINER_ADDRESS
+        .parse::<Address<_>>()
+        .unwrap()
+        .require_network(Network::Regtest)
+        .unwrap()
+}
+
+fn balances(trusted: f64, immature: f64) -> Value {
+    let mut balances = load_fixture("getbalances_empty");
+    balances["mine"]["trusted"] = trusted.into();
+    balances["mine"]["immature"] = immature.into();
+    balances
+}
+
+fn mock_miner(balances: Value) -> MockRpc {
+    let mock = MockRpc::start();
+    mock.reply_value("getbalances", balances)
+        .reply_value("generatetoaddress", json!([BLOCK]));
+    mock
+}
+
+fn blocks_generated(mock: &MockRpc) -> usize {
+    mock.calls()
+        .iter()
+        .filter(|c| c.method == "generatetoaddress")
+        .inspect(|c| assert_eq!(c.params, json!([1, MINER_ADDRESS])))
+        .count()
+}
+
+#[test]
+fn spendable_wallet_mines_nothing() {
+    let mock = mock_miner(load_fixture("getbalances"));
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+
+    let maturity =
+        mining::mine_until_spendable(&miner, &address(), |_| panic!("no block to report")).unwrap();
+
+    assert_eq!(
+        maturity,
+        Maturity {
+            blocks_mined: 0,
+            trusted: Amount::from_btc(20.0).unwrap(),
+            immature: Amount::ZERO,
+        }
+    );
+    assert_eq!(blocks_generated(&mock), 0);
+}
+
+#[test]
+fn mines_until_the_first_reward_matures() {
+    let mock = mock_miner(balances(0.0, 0.0));
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let reported = Cell::new(0);
+
+    // The node's balances after each block: every reward is immature until the
+    // block COINBASE_MATURITY blocks later matures the first one
+    let maturity = mining::mine_until_spendable(&miner, &address(), |progress| {
+        reported.set(reported.get() + 1);
+        assert_eq!(progress.blocks_mined, reported.get());
+        let mined = progress.blocks_mined as f64;
+        let next = if progress.blocks_mined + 1 == COINBASE_MATURITY + 1 {
+            balances(50.0, COINBASE_MATURITY as f64 * 50.0)
+        } else {
+            balances(0.0, (mined + 1.0) * 50.0)
+        };
+        mock.reply_value("getbalances", next);
+    })
+    .unwrap();
+
+    assert_eq!(maturity.blocks_mined, COINBASE_MATURITY + 1);
+    assert_eq!(maturity.trusted, Amount::from_btc(50.0).unwrap());
+    assert_eq!(maturity.immature, Amount::from_btc(5000.0).unwrap());
+    assert_eq!(reported.get(), COINBASE_MATURITY + 1);
+    assert_eq!(blocks_generated(&mock), 101);
+    assert!(
+        maturity.explanation().contains("It took 101 block(s)"),
+        "{}",
+        maturity.explanation()
+    );
+}
+
+#[test]
+fn gives_up_when_no_reward_ever_matures() {
+    // E.g. a regtest chain past its last halving, whose rewards are zero
+    let mock = mock_miner(balances(0.0, 0.0));
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+
+    let e = mining::mine_until_spendable(&miner, &address(), |_| {}).unwrap_err();
+
+    match &e {
+        Error::NotSpendable { wallet, blocks } => {
+            assert_eq!(wallet, "Miner");
+            assert_eq!(*blocks, COINBASE_MATURITY + 1);
+        }
+        e => panic!("expected NotSpendable, got {e:?}"),
+    }
+    assert_eq!(e.exit_code(), 6);
+    assert_eq!(blocks_generated(&mock), 101);
+}
@@ -0,0 +1,290 @@
+//! An in-process stand-in for Bitcoin Core's JSON-RPC server.
+//!
+//! Each method answers with a canned result, usually a fixture recorded from a
+//! regtest node (`tests/fixtures/<name>.json` holds the `result` member of the
+//! reply), or with a Core error. Every request is recorded so tests can check
+//! which calls were made, in which order and on which wallet endpoint.
+
+// Each test crate that includes this module uses only part of it.
+#![allow(dead_code)]
+
+use capstone::auth::Credentials;
+use capstone::network::Chain;
+use capstone::report::ReportFormat;
+use capstone::{Config, Node};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::{fs, thread};
+
+// Core's RPC_METHOD_NOT_FOUND.
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// One request the mock received.
+#[derive(Debug, Clone)]
+pub struct Call {
+    /// `/` for node calls, `/wallet/<name>` for wallet calls.
+    pub path: String,
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Debug, Clone)]
+enum Reply {
+    Result(Value),
+    Error { code: i32, message: String },
+}
+
+#[derive(Default)]
+struct State {
+    replies: HashMap<String, Reply>,
+    // Replies for a method called with a given first parameter, or on a given
+    // wallet's endpoint; these win
+    replies_for: HashMap<(String, Value), Reply>,
+    wallet_replies: HashMap<(String, String), Reply>,
+    // Both at once, e.g. `getaddressinfo` of one address on one wallet; these win over all
+    wallet_replies_for: HashMap<(String, String, Value), Reply>,
+    calls: Vec<Call>,
+}
+
+pub struct MockRpc {
+    port: u16,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockRpc {
+    /// Starts serving on a free local port. Methods without a reply answer
+    /// "Method not found", like Core does for unknown methods.
+    pub fn start() -> MockRpc {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock RPC port");
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = Arc::clone(&shared);
+                thread::spawn(move || serve(stream, &state));
+            }
+        });
+
+        MockRpc { port, state }
+    }
+
+    /// Answers `method` with the fixture `tests/fixtures/<fixture>.json`.
+    pub fn reply(&self, method: &str, fixture: &str) -> &MockRpc {
+        self.reply_value(method, load_fixture(fixture))
+    }
+
+    /// Answers `method` with `result`.
+    pub fn reply_value(&self, method: &str, result: Value) -> &MockRpc {
+        self.set(method, Reply::Result(result))
+    }
+
+    /// Answers `method` with the fixture only when its first parameter is `first`,
+    /// e.g. `getmempoolentry` for one txid.
+    pub fn reply_for(&self, method: &str, first: impl Into<Value>, fixture: &str) -> &MockRpc {
+        self.reply_value_for(method, first, load_fixture(fixture))
+    }
+
+    /// Answers `method` with `result` only when its first parameter is `first`.
+    pub fn reply_value_for(
+        &self,
+        method: &str,
+        first: impl Into<Value>,
+        result: Value,
+    ) -> &MockRpc {
+        let mut state = self.state.lock().unwrap();
+        state
+            .replies_for
+            .insert((method.to_owned(), first.into()), Reply::Result(result));
+        self
+    }
+
+    /// Answers `method` with the fixture only when called on `wallet`'s endpoint.
+    pub fn reply_on(&self, wallet: &str, method: &str, fixture: &str) -> &MockRpc {
+        self.reply_value_on(wallet, method, load_fixture(fixture))
+    }
+
+    /// Answers `method` with `result` only when called on `wallet`'s endpoint.
+    pub fn reply_value_on(&self, wallet: &str, method: &str, result: Value) -> &MockRpc {
+        let mut state = self.state.lock().unwrap();
+        state.wallet_replies.insert(
+            (format!("/wallet/{wallet}"), method.to_owned()),
+            Reply::Result(result),
+        );
+        self
+    }
+
+    /// Answers `method` with `result` only when called on `wallet`'s endpoint
+    /// with `first` as its first parameter.
+    pub fn reply_value_on_for(
+        &self,
+        wallet: &str,
+        method: &str,
+        first: impl Into<Value>,
+        result: Value,
+    ) -> &MockRpc {
+        let mut state = self.state.lock().unwrap();
+        state.wallet_replies_for.insert(
+            (format!("/wallet/{wallet}"), method.to_owned(), first.into()),
+            Reply::Result(result),
+        );
+        self
+    }
+
+    /// Answers `method` with a Core RPC error.
+    pub fn fail(&self, method: &str, code: i32, message: &str) -> &MockRpc {
+        self.set(
+            method,
+            Reply::Error {
+                code,
+                message: message.to_owned(),
+            },
+        )
+    }
+
+    fn set(&self, method: &str, reply: Reply) -> &MockRpc {
+        let mut state = self.state.lock().unwrap();
+        state.replies.insert(method.to_owned(), reply);
+        self
+    }
+
+    /// Every request received so far, oldest first.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Names of the methods called so far, oldest first.
+    pub fn methods(&self) -> Vec<String> {
+        self.calls().into_iter().map(|c| c.method).collect()
+    }
+
+    /// Connects a [`Node`] with [`MockRpc::config`], answering the
+    /// `getblockchaininfo` it starts with.
+    pub fn node(&self) -> Node {
+        self.reply("getblockchaininfo", "getblockchaininfo");
+        Node::connect(self.config()).unwrap()
+    }
+
+    /// Settings pointing the library at the mock with user/password auth.
+    pub fn config(&self) -> Config {
+        Config {
+            rpc_host: "127.0.0.1".to_owned(),
+            rpc_port: self.port,
+            rpc_user: Some("alice".to_owned()),
+            rpc_pass: Some("password".to_owned()),
+            rpc_cookie: None,
+            datadir: None,
+            network: Some(Chain::Regtest),
+            miner_wallet: "Miner".to_owned(),
+            trader_wallet: "Trader".to_owned(),
+            output: "out.txt".into(),
+            format: ReportFormat::Text,
+            passphrase_from: None,
+            unlock_timeout: 60,
+        }
+    }
+
+    pub fn credentials(&self) -> Credentials {
+        Credentials::resolve(&self.config()).expect("user/password credentials")
+    }
+}
+
+pub fn load_fixture(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{name}.json"));
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read fixture {}: {e}", path.display()));
+    serde_json::from_str(&text)
+        .unwrap_or_else(|e| panic!("invalid fixture {}: {e}", path.display()))
+}
+
+// Answers HTTP/1.1 requests on one keep-alive connection until the client hangs up.
+fn serve(stream: TcpStream, state: &Mutex<State>) {
+    let mut writer = stream.try_clone().expect("clone mock connection");
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or("/")
+            .to_owned();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 {
+                return;
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        let request: Value = serde_json::from_slice(&body).expect("JSON-RPC request body");
+        let response = respond(path, &request, state).to_string();
+
+        // One write: a separate head and body stall on Nagle and delayed ACKs
+        let reply = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{response}",
+            response.len()
+        );
+        if writer.write_all(reply.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn respond(path: String, request: &Value, state: &Mutex<State>) -> Value {
+    let method = request["method"].as_str().unwrap_or_default().to_owned();
+    let mut state = state.lock().unwrap();
+    state.calls.push(Call {
+        path: path.clone(),
+        method: method.clone(),
+        params: request["params"].clone(),
+    });
+
+    let first = request["params"][0].clone();
+    let reply = state
+        .wallet_replies_for
+        .get(&(path.clone(), method.clone(), first.clone()))
+        .or_else(|| state.replies_for.get(&(method.clone(), first)))
+        .or_else(|| state.wallet_replies.get(&(path.clone(), method.clone())))
+        .or_else(|| state.replies.get(&method));
+    let (result, error) = match reply {
+        Some(Reply::Result(result)) => (result.clone(), Value::Null),
+        Some(Reply::Error { code, message }) => {
+            (Value::Null, json!({ "code": code, "message": message }))
+        }
+        None => (
+            Value::Null,
+            json!({ "code": METHOD_NOT_FOUND, "message": "Method not found" }),
+        ),
+    };
+    json!({
+        "jsonrpc": request["jsonrpc"],
+        "id": request["id"],
+        "result": result,
+        "error": error,
+    })
+}
@@ -0,0 +1,309 @@
+//! Offline tests of multisig setup and signing rounds against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use capstone::multisig::{self, MultisigError, SigningRound};
+use capstone::wallet::WalletError;
+use capstone::{Error, Node, WalletHandle};
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::{json, Value};
+
+const COSIGNERS: [&str; 3] = ["Miner", "Trader", "Cosigner"];
+
+fn cosigners(node: &Node) -> Vec<WalletHandle> {
+    COSIGNERS
+        .iter()
+        .map(|name| node.loaded_wallet(name).unwrap())
+        .collect()
+}
+
+fn descriptor(fixture: &str) -> String {
+    load_fixture(fixture)["descriptor"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+// Checksums of each cosigner's receive and change signing descriptors.
+const SIGNING_CHECKSUMS: [[&str; 2]; 3] = [
+    ["ckudyc6j", "fzeved22"],
+    ["fpuwmncf", "c4e0xxg3"],
+    ["mkn52lvj", "2zk4h2u2"],
+];
+
+fn listed_private(cosigner: &str) -> Value {
+    load_fixture(&format!(
+        "listdescriptors_private_{}",
+        cosigner.to_lowercase()
+    ))
+}
+
+// `pk(...)` over the cosigner's BIP 48 account, with its master xprv.
+fn signing_descriptor(cosigner: &str, branch: usize) -> String {
+    let listed = listed_private(cosigner);
+    let wpkh = listed["descriptors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["desc"].as_str().unwrap())
+        .find(|d| d.starts_with("wpkh("))
+        .unwrap();
+    let (master, _) = wpkh["wpkh(".len()..].split_once('/').unwrap();
+    format!("pk({master}/48h/1h/0h/2h/{branch}/*)")
+}
+
+// Replies for three unencrypted cosigners, none of them set up for the
+// multisig yet, and the descriptors of a 2-of-3 over their keys.
+fn mock_cosigners() -> MockRpc {
+    let mock = MockRpc::start();
+    for (cosigner, checksums) in COSIGNERS.iter().zip(SIGNING_CHECKSUMS) {
+        mock.reply_value_on(cosigner, "listdescriptors", listed_private(cosigner));
+        for (branch, checksum) in checksums.into_iter().enumerate() {
+            let desc = signing_descriptor(cosigner, branch);
+            mock.reply_value_for(
+                "getdescriptorinfo",
+                desc.as_str(),
+                json!({
+                    "descriptor": format!("{desc}#{checksum}"),
+                    "checksum": checksum,
+                    "isrange": true,
+                    "issolvable": true,
+                    "hasprivatekeys": true
+                }),
+            );
+        }
+    }
+    for (fixture, branch) in [
+        ("getdescriptorinfo_receive", "/0/*"),
+        ("getdescriptorinfo_change", "/1/*"),
+    ] {
+        let desc = descriptor(fixture);
+        let (desc, _) = desc.split_once('#').unwrap();
+        assert!(desc.contains(branch));
+        mock.reply_for("getdescriptorinfo", desc, fixture);
+    }
+    mock.reply("getwalletinfo", "getwalletinfo")
+        .reply("listwallets", "listwallets")
+        .reply("listwalletdir", "listwalletdir")
+        .reply("createwallet", "createwallet")
+        .reply("importdescriptors", "importdescriptors");
+    mock
+}
+
+#[test]
+fn create_imports_sorted_multisig_descriptors() {
+    let mock = mock_cosigners();
+    let node = mock.node();
+
+    let created = multisig::create(&node, "Vault", 2, &cosigners(&node)).unwrap();
+
+    assert_eq!(created.coordinator.name(), "Vault");
+    assert_eq!(created.cosigners, COSIGNERS);
+    assert_eq!(
+        created.receive_descriptor,
+        descriptor("getdescriptorinfo_receive")
+    );
+    assert_eq!(
+        created.change_descriptor,
+        descriptor("getdescriptorinfo_change")
+    );
+    // Keys of a dedicated BIP 48 account, not those of the single-key addresses
+    assert!(created.receive_descriptor.starts_with(
+        "wsh(sortedmulti(2,[552288cc/48h/1h/0h/2h]tpubDER6QPNYMdbAWvs8yvWeLcCFBjNpo7ANhPht4GKgkS2EYDMwSe6YyWKBTmdxHtA1PdhJRdp8hi43jRUEUP8NaWorYoMzUH8HJobBvW9HLZG/0/*,"
+    ));
+    assert!(!created.receive_descriptor.contains("/84h/"));
+
+    let calls = mock.calls();
+    let create = calls.iter().find(|c| c.method == "createwallet").unwrap();
+    assert_eq!(
+        create.params,
+        json!(["Vault", true, true, null, false, true, null])
+    );
+    let imports: Vec<_> = calls
+        .iter()
+        .filter(|c| c.method == "importdescriptors")
+        .collect();
+    assert_eq!(imports.len(), 4);
+    for ((import, cosigner), checksums) in imports.iter().zip(COSIGNERS).zip(SIGNING_CHECKSUMS) {
+        assert_eq!(import.path, format!("/wallet/{cosigner}"));
+        assert_eq!(
+            import.params,
+            json!([[
+                {
+                    "desc": format!("{}#{}", signing_descriptor(cosigner, 0), checksums[0]),
+                    "active": false,
+                    "timestamp": "now"
+                },
+                {
+                    "desc": format!("{}#{}", signing_descriptor(cosigner, 1), checksums[1]),
+                    "active": false,
+                    "timestamp": "now"
+                }
+            ]])
+        );
+    }
+    let import = imports[3];
+    assert_eq!(import.path, "/wallet/Vault");
+    assert_eq!(
+        import.params,
+        json!([[
+            {
+                "desc": created.receive_descriptor,
+                "active": true,
+                "internal": false,
+                "timestamp": "now"
+            },
+            {
+                "desc": created.change_descriptor,
+                "active": true,
+                "internal": true,
+                "timestamp": "now"
+            }
+        ]])
+    );
+}
+
+#[test]
+fn create_again_imports_nothing() {
+    let mock = mock_cosigners();
+    for (cosigner, checksums) in COSIGNERS.iter().zip(SIGNING_CHECKSUMS) {
+        let mut listed = listed_private(cosigner);
+        let descriptors = listed["descriptors"].as_array_mut().unwrap();
+        for (branch, checksum) in checksums.into_iter().enumerate() {
+            descriptors.push(json!({
+                "desc": format!("{}#{checksum}", signing_descriptor(cosigner, branch)),
+                "timestamp": 1735689600,
+                "active": false,
+                "range": [0, 999],
+                "next": 0,
+                "next_index": 0
+            }));
+        }
+        mock.reply_value_on(cosigner, "listdescriptors", listed);
+    }
+    mock.reply_value(
+        "listwallets",
+        json!(["Miner", "Trader", "Cosigner", "Vault"]),
+    )
+    .reply_on("Vault", "getwalletinfo", "getwalletinfo_watch_only")
+    .reply_on("Vault", "listdescriptors", "listdescriptors_vault");
+    let node = mock.node();
+
+    let created = multisig::create(&node, "Vault", 2, &cosigners(&node)).unwrap();
+
+    assert_eq!(
+        created.receive_descriptor,
+        descriptor("getdescriptorinfo_receive")
+    );
+    let methods = mock.methods();
+    assert!(!methods.contains(&"createwallet".to_owned()), "{methods:?}");
+    assert!(
+        !methods.contains(&"importdescriptors".to_owned()),
+        "{methods:?}"
+    );
+}
+
+#[test]
+fn create_reports_rejected_descriptor() {
+    let mock = mock_cosigners();
+    mock.reply_on("Vault", "importdescriptors", "importdescriptors_failed");
+    let node = mock.node();
+
+    let e = multisig::create(&node, "Vault", 2, &cosigners(&node))
+        .err()
+        .unwrap();
+
+    assert!(
+        matches!(&e, Error::Wallet(WalletError::ImportFailed { wallet, .. }) if wallet == "Vault"),
+        "{e:?}"
+    );
+    assert_eq!(e.exit_code(), 6);
+}
+
+#[test]
+fn create_rejects_cosigner_without_master_key() {
+    // Public descriptors only, as a watch-only wallet would list
+    let mock = mock_cosigners();
+    mock.reply_on("Cosigner", "listdescriptors", "listdescriptors_trader");
+    let node = mock.node();
+    let e = multisig::create(&node, "Vault", 2, &cosigners(&node))
+        .err()
+        .unwrap();
+    assert!(
+        matches!(&e, Error::Multisig(MultisigError::UnsupportedDescriptor(wallet)) if wallet == "Cosigner"),
+        "{e:?}"
+    );
+
+    // No wpkh descriptor at all
+    let mock = mock_cosigners();
+    let mut listed = listed_private("Cosigner");
+    listed["descriptors"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|d| !d["desc"].as_str().unwrap().starts_with("wpkh("));
+    mock.reply_value_on("Cosigner", "listdescriptors", listed);
+    let node = mock.node();
+    let e = multisig::create(&node, "Vault", 2, &cosigners(&node))
+        .err()
+        .unwrap();
+    assert!(
+        matches!(&e, Error::Multisig(MultisigError::NoCosignerKey(wallet)) if wallet == "Cosigner"),
+        "{e:?}"
+    );
+    assert!(!mock.methods().contains(&"createwallet".to_owned()));
+}
+
+#[test]
+fn create_rejects_impossible_threshold() {
+    let mock = MockRpc::start();
+    let node = mock.node();
+
+    let e = multisig::create(&node, "Vault", 4, &cosigners(&node))
+        .err()
+        .unwrap();
+
+    assert!(
+        matches!(
+            e,
+            Error::Multisig(MultisigError::InvalidThreshold {
+                threshold: 4,
+                cosigners: 3
+            })
+        ),
+        "{e:?}"
+    );
+    assert_eq!(e.exit_code(), 2);
+    assert_eq!(mock.methods(), ["getblockchaininfo"]);
+}
+
+#[test]
+fn collect_signatures_stops_once_complete() {
+    let mock = MockRpc::start();
+    mock.reply_on("Miner", "walletprocesspsbt", "walletprocesspsbt_incomplete")
+        .reply_on("Trader", "walletprocesspsbt", "walletprocesspsbt")
+        .reply_on("Cosigner", "walletprocesspsbt", "walletprocesspsbt");
+    let node = mock.node();
+    let unsigned = load_fixture("send_incomplete")["psbt"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let signed = multisig::collect_signatures(&cosigners(&node), &unsigned).unwrap();
+
+    assert!(signed.complete);
+    assert_eq!(
+        signed.rounds,
+        [
+            SigningRound {
+                signer: "Miner".to_owned(),
+                complete: false
+            },
+            SigningRound {
+                signer: "Trader".to_owned(),
+                complete: true
+            }
+        ]
+    );
+    let paths: Vec<String> = mock.calls().into_iter().skip(1).map(|c| c.path).collect();
+    assert_eq!(paths, ["/wallet/Miner", "/wallet/Trader"]);
+}
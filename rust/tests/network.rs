@@ -0,0 +1,88 @@
+//! Offline tests of chain detection against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::Network;
+use capstone::network::{self, Chain, NetworkError};
+use capstone::{Config, Error, Node};
+use mock_rpc::{load_fixture, MockRpc};
+
+fn mock_chain(chain: &str) -> MockRpc {
+    let mock = MockRpc::start();
+    let mut info = load_fixture("getblockchaininfo");
+    info["chain"] = chain.into();
+    mock.reply_value("getblockchaininfo", info);
+    mock
+}
+
+fn detect(mock: &MockRpc, expected: Option<Chain>) -> Result<Chain, NetworkError> {
+    let rpc = mock
+        .credentials()
+        .connect(&mock.config().rpc_url())
+        .unwrap();
+    network::detect(&rpc, expected)
+}
+
+#[test]
+fn detect_maps_core_chain_names() {
+    for (name, chain) in [
+        ("main", Chain::Main),
+        ("test", Chain::Testnet3),
+        ("testnet4", Chain::Testnet4),
+        ("signet", Chain::Signet),
+        ("regtest", Chain::Regtest),
+    ] {
+        let mock = mock_chain(name);
+
+        assert_eq!(detect(&mock, None).unwrap(), chain, "{name}");
+        assert_eq!(chain.core_name(), name);
+    }
+}
+
+#[test]
+fn detect_rejects_unknown_chain() {
+    let mock = mock_chain("liquidv1");
+
+    match detect(&mock, None) {
+        Err(NetworkError::UnknownChain(name)) => assert_eq!(name, "liquidv1"),
+        other => panic!("expected an unknown chain, got {other:?}"),
+    }
+}
+
+#[test]
+fn detect_checks_the_expected_chain() {
+    let mock = mock_chain("testnet4");
+
+    assert_eq!(
+        detect(&mock, Some(Chain::Testnet4)).unwrap(),
+        Chain::Testnet4
+    );
+    match detect(&mock, Some(Chain::Testnet3)) {
+        Err(NetworkError::Mismatch { expected, actual }) => {
+            assert_eq!((expected, actual), (Chain::Testnet3, Chain::Testnet4));
+        }
+        other => panic!("expected a mismatch, got {other:?}"),
+    }
+}
+
+#[test]
+fn node_takes_the_detected_chain() {
+    let mock = mock_chain("testnet4");
+
+    let node = Node::connect(Config {
+        network: None,
+        ..mock.config()
+    })
+    .unwrap();
+
+    assert_eq!(node.chain(), Chain::Testnet4);
+    // Testnet4 addresses are rendered like testnet3's
+    assert_eq!(node.network(), Network::Testnet);
+
+    let e = Node::connect(mock.config()).err().unwrap();
+    assert!(
+        matches!(e, Error::Network(NetworkError::Mismatch { .. })),
+        "{e:?}"
+    );
+    assert_eq!(e.exit_code(), 4);
+}
@@ -0,0 +1,240 @@
+//! Offline tests of passphrase sources and wallet unlocking against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use capstone::passphrase::{PassphraseError, PassphraseSource};
+use capstone::{Config, Error, Node};
+use mock_rpc::MockRpc;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::{env, fs, process};
+
+const SECRET: &str = "correct horse battery staple";
+
+fn connect(mock: &MockRpc, passphrase_from: Option<PassphraseSource>) -> Node {
+    mock.reply("getblockchaininfo", "getblockchaininfo");
+    Node::connect(Config {
+        passphrase_from,
+        unlock_timeout: 30,
+        ..mock.config()
+    })
+    .unwrap()
+}
+
+#[test]
+fn sources_parse_and_render() {
+    for source in ["prompt", "env:WALLET_PASS", "fd:3"] {
+        let parsed: PassphraseSource = source.parse().unwrap();
+        assert_eq!(parsed.to_string(), source);
+    }
+    assert_eq!(
+        "env:WALLET_PASS".parse(),
+        Ok(PassphraseSource::Env("WALLET_PASS".to_owned()))
+    );
+    for invalid in ["", "env:", "fd:x", "file:/tmp/pass", SECRET] {
+        assert!(invalid.parse::<PassphraseSource>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn passphrase_is_read_from_env_and_fd_but_never_shown() {
+    let var = format!("CAPSTONE_TEST_PASSPHRASE_{}", process::id());
+    env::set_var(&var, SECRET);
+    let source = PassphraseSource::Env(var.clone());
+    let passphrase = source.read("Miner").unwrap();
+    assert_eq!(passphrase.expose(), SECRET);
+    assert!(!format!("{passphrase:?}").contains(SECRET));
+
+    env::set_var(&var, "");
+    assert!(matches!(
+        source.read("Miner"),
+        Err(PassphraseError::Empty(_))
+    ));
+    env::remove_var(&var);
+    let err = source.read("Miner").unwrap_err();
+    assert!(matches!(err, PassphraseError::Unavailable { .. }));
+
+    let path = env::temp_dir().join(format!("capstone-passphrase-{}", process::id()));
+    writeln!(File::create(&path).unwrap(), "{SECRET}").unwrap();
+    let file = File::open(&path).unwrap();
+    let read = PassphraseSource::Fd(file.as_raw_fd() as u32).read("Miner");
+    fs::remove_file(&path).unwrap();
+    assert_eq!(read.unwrap().expose(), SECRET);
+}
+
+#[test]
+fn unlock_is_bounded_and_relocks_after_use() {
+    let mock = MockRpc::start();
+    mock.reply("getwalletinfo", "getwalletinfo_encrypted")
+        .reply("walletpassphrase", "walletpassphrase")
+        .reply("walletlock", "walletlock");
+    let var = format!("CAPSTONE_TEST_UNLOCK_{}", process::id());
+    env::set_var(&var, SECRET);
+    let node = connect(&mock, Some(PassphraseSource::Env(var.clone())));
+    let miner = node.loaded_wallet("Miner").unwrap();
+
+    let unlocked = node.unlock(&miner).unwrap();
+    env::remove_var(&var);
+    assert!(unlocked.is_some());
+    assert_eq!(mock.methods()[1..], ["getwalletinfo", "walletpassphrase"]);
+    drop(unlocked);
+
+    let calls = mock.calls();
+    assert_eq!(calls[2].path, "/wallet/Miner");
+    assert_eq!(calls[2].params, json!([SECRET, 30]));
+    assert_eq!(calls[3].method, "walletlock");
+
+    // The passphrase is read once per wallet
+    node.unlock(&miner).unwrap();
+    assert_eq!(mock.methods().len(), 7);
+}
+
+#[test]
+fn each_wallet_gets_its_own_passphrase() {
+    let mock = MockRpc::start();
+    mock.reply("getwalletinfo", "getwalletinfo_encrypted")
+        .reply("walletpassphrase", "walletpassphrase")
+        .reply("walletlock", "walletlock");
+    let var = format!("CAPSTONE_TEST_PER_WALLET_{}", process::id());
+    let node = connect(&mock, Some(PassphraseSource::Env(var.clone())));
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    env::set_var(&var, SECRET);
+    drop(node.unlock(&miner).unwrap());
+    env::set_var(&var, "another secret");
+    drop(node.unlock(&trader).unwrap());
+    env::remove_var(&var);
+    // Miner's passphrase is cached, Trader's was read for Trader
+    drop(node.unlock(&miner).unwrap());
+
+    let unlocks: Vec<(String, Value)> = mock
+        .calls()
+        .into_iter()
+        .filter(|c| c.method == "walletpassphrase")
+        .map(|c| (c.path, c.params[0].clone()))
+        .collect();
+    assert_eq!(
+        unlocks,
+        [
+            ("/wallet/Miner".to_owned(), json!(SECRET)),
+            ("/wallet/Trader".to_owned(), json!("another secret")),
+            ("/wallet/Miner".to_owned(), json!(SECRET)),
+        ]
+    );
+    // Nothing cached for a wallet whose passphrase was never read
+    let cosigner = node.loaded_wallet("Cosigner").unwrap();
+    assert!(matches!(
+        node.unlock(&cosigner).err().unwrap(),
+        Error::Passphrase(PassphraseError::Unavailable { .. })
+    ));
+}
+
+#[test]
+fn wallet_encrypt_uses_the_wallets_passphrase() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets")
+        .reply("getwalletinfo", "getwalletinfo")
+        .reply("encryptwallet", "encryptwallet")
+        .reply("walletpassphrase", "walletpassphrase")
+        .reply("walletlock", "walletlock");
+    let var = format!("CAPSTONE_TEST_ENCRYPT_{}", process::id());
+    let node = connect(&mock, Some(PassphraseSource::Env(var.clone())));
+
+    // What `wallet encrypt Trader` does
+    let trader = node.ensure_wallet("Trader").unwrap();
+    assert!(!trader.is_encrypted().unwrap());
+    env::set_var(&var, SECRET);
+    let encrypted = node
+        .passphrase("Trader")
+        .and_then(|passphrase| trader.encrypt(&passphrase));
+    env::remove_var(&var);
+    encrypted.unwrap();
+
+    let encrypt = mock.calls().pop().unwrap();
+    assert_eq!(encrypt.method, "encryptwallet");
+    assert_eq!(encrypt.path, "/wallet/Trader");
+    assert_eq!(encrypt.params, json!([SECRET]));
+    // Later signing in the same run unlocks with the same passphrase
+    mock.reply("getwalletinfo", "getwalletinfo_encrypted");
+    drop(node.unlock(&trader).unwrap());
+    let unlock = mock
+        .calls()
+        .into_iter()
+        .find(|c| c.method == "walletpassphrase")
+        .unwrap();
+    assert_eq!(unlock.params, json!([SECRET, 30]));
+}
+
+#[test]
+fn unencrypted_wallets_need_no_passphrase() {
+    let mock = MockRpc::start();
+    mock.reply("getwalletinfo", "getwalletinfo");
+    let node = connect(&mock, None);
+    let miner = node.loaded_wallet("Miner").unwrap();
+
+    assert!(node.unlock(&miner).unwrap().is_none());
+    assert!(!mock.methods().contains(&"walletpassphrase".to_owned()));
+
+    mock.reply("getwalletinfo", "getwalletinfo_encrypted");
+    let err = node.unlock(&miner).err().unwrap();
+    assert!(matches!(
+        err,
+        Error::Passphrase(PassphraseError::NotConfigured(ref wallet)) if wallet == "Miner"
+    ));
+    assert_eq!(err.exit_code(), 2);
+}
+
+#[test]
+fn new_wallets_are_created_with_the_passphrase() {
+    let mock = MockRpc::start();
+    mock.reply("listwalletdir", "listwalletdir_empty")
+        .reply("listwallets", "listwallets_empty")
+        .reply("createwallet", "createwallet");
+    let var = format!("CAPSTONE_TEST_CREATE_{}", process::id());
+    env::set_var(&var, SECRET);
+    let node = connect(&mock, Some(PassphraseSource::Env(var.clone())));
+
+    node.ensure_wallet("Miner").unwrap();
+    env::remove_var(&var);
+
+    let create = mock
+        .calls()
+        .into_iter()
+        .find(|c| c.method == "createwallet")
+        .unwrap();
+    assert_eq!(create.params[0], "Miner");
+    assert_eq!(create.params[3], SECRET);
+    assert_eq!(
+        mock.methods()
+            .iter()
+            .filter(|m| *m == "listwalletdir")
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn existing_wallets_need_no_passphrase() {
+    // Reading from an unset variable fails, so any read would fail the run
+    let var = format!("CAPSTONE_TEST_UNSET_{}", process::id());
+    for (listed, dir) in [
+        (json!(["Miner"]), json!({ "wallets": [] })),
+        (
+            json!(["/data/regtest/wallets/Miner"]),
+            json!({ "wallets": [{ "name": "Miner" }] }),
+        ),
+    ] {
+        let mock = MockRpc::start();
+        mock.reply_value("listwallets", listed)
+            .reply_value("listwalletdir", dir)
+            .reply("loadwallet", "loadwallet");
+        let node = connect(&mock, Some(PassphraseSource::Env(var.clone())));
+
+        node.ensure_wallet("Miner").unwrap();
+
+        assert!(!mock.methods().contains(&"createwallet".to_owned()));
+    }
+}
@@ -0,0 +1,150 @@
+//! Offline tests of the PSBT pipeline against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::{Address, Amount, Network, Txid};
+use capstone::psbt::{self, Finalized};
+use capstone::send::SendOptions;
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::json;
+use std::str::FromStr;
+use std::{env, fs, process};
+
+const TXID: &str = "6c2fae5e0e51422beb17fde022dca96279827a9f20852e8179f6d6dadaef9505";
+const TRADER_ADDRESS: &str = "bcrt1qqyqszqgpqyqszqgpqyqszqgpqyqszqgpvxat9t";
+const CHANGE_ADDRESS: &str = "bcrt1qqgpqyqszqgpqyqszqgpqyqszqgpqyqszazmwwa";
+
+fn address(address: &str) -> Address {
+    Address::from_str(address)
+        .unwrap()
+        .require_network(Network::Regtest)
+        .unwrap()
+}
+
+fn fixture_str(name: &str, member: &str) -> String {
+    load_fixture(name)[member].as_str().unwrap().to_owned()
+}
+
+#[test]
+fn create_funds_psbt_with_options() {
+    let mock = MockRpc::start();
+    mock.reply("walletcreatefundedpsbt", "walletcreatefundedpsbt");
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let options = SendOptions::new()
+        .conf_target(6)
+        .change_address(address(CHANGE_ADDRESS))
+        .locktime(101)
+        .replaceable(true);
+
+    let funded = psbt::create(
+        &miner,
+        &[(address(TRADER_ADDRESS), Amount::from_btc(20.0).unwrap())],
+        &options,
+    )
+    .unwrap();
+
+    assert_eq!(funded.psbt, fixture_str("walletcreatefundedpsbt", "psbt"));
+    assert_eq!(funded.fee, Amount::from_sat(1410));
+    assert_eq!(funded.change_position, Some(1));
+    let call = mock.calls().pop().unwrap();
+    assert_eq!(call.path, "/wallet/Miner");
+    assert_eq!(
+        call.params,
+        json!([
+            [],
+            [{ TRADER_ADDRESS: 20.0 }],
+            101,
+            {
+                "change_address": CHANGE_ADDRESS,
+                "replaceable": true,
+                "conf_target": 6
+            },
+            true
+        ])
+    );
+}
+
+#[test]
+fn inspect_summarizes_outputs_fee_and_next_role() {
+    let mock = MockRpc::start();
+    mock.reply("decodepsbt", "decodepsbt")
+        .reply("analyzepsbt", "analyzepsbt");
+    let node = mock.node();
+
+    let summary = psbt::inspect(node.rpc(), &fixture_str("send_incomplete", "psbt")).unwrap();
+
+    assert_eq!(summary.txid, Txid::from_str(TXID).unwrap());
+    assert_eq!(summary.inputs, 1);
+    let outputs: Vec<_> = summary
+        .outputs
+        .iter()
+        .map(|o| (o.address.as_deref().unwrap(), o.amount.to_sat()))
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            (TRADER_ADDRESS, 2_000_000_000),
+            (CHANGE_ADDRESS, 2_999_998_590)
+        ]
+    );
+    assert_eq!(summary.fee, Some(Amount::from_sat(1410)));
+    assert_eq!(summary.estimated_vsize, Some(141));
+    assert_eq!(summary.estimated_fee_rate, Some(10.0));
+    assert_eq!(summary.next, "signer");
+}
+
+#[test]
+fn sign_finalize_and_broadcast() {
+    let mock = MockRpc::start();
+    mock.reply("walletprocesspsbt", "walletprocesspsbt")
+        .reply("finalizepsbt", "finalizepsbt")
+        .reply("sendrawtransaction", "sendrawtransaction");
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let unsigned = fixture_str("send_incomplete", "psbt");
+
+    let signed = psbt::sign(&miner, &unsigned).unwrap();
+    assert!(signed.complete);
+    let hex = match psbt::finalize(node.rpc(), &signed.psbt).unwrap() {
+        Finalized::Complete(hex) => hex,
+        Finalized::Incomplete(_) => panic!("expected a finalized transaction"),
+    };
+    let txid = psbt::broadcast(node.rpc(), &hex).unwrap();
+
+    assert_eq!(txid, Txid::from_str(TXID).unwrap());
+    assert_eq!(hex, fixture_str("finalizepsbt", "hex"));
+    let calls = mock.calls();
+    assert_eq!(calls[1].path, "/wallet/Miner");
+    assert_eq!(
+        calls[1].params,
+        json!([unsigned, true, "DEFAULT", true, false])
+    );
+    assert_eq!(calls[2].path, "/");
+    assert_eq!(calls[2].params, json!([signed.psbt, true]));
+    assert_eq!(calls[3].params, json!([hex]));
+}
+
+#[test]
+fn finalize_returns_psbt_when_signatures_are_missing() {
+    let mock = MockRpc::start();
+    mock.reply("finalizepsbt", "finalizepsbt_incomplete");
+    let node = mock.node();
+    let unsigned = fixture_str("send_incomplete", "psbt");
+
+    let finalized = psbt::finalize(node.rpc(), &unsigned).unwrap();
+
+    assert_eq!(finalized, Finalized::Incomplete(unsigned));
+}
+
+#[test]
+fn save_and_load_round_trip() {
+    let path = env::temp_dir().join(format!("capstone-psbt-{}.txt", process::id()));
+    let unsigned = fixture_str("send_incomplete", "psbt");
+
+    psbt::save(&path, &unsigned).unwrap();
+    let loaded = psbt::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.unwrap(), unsigned);
+}
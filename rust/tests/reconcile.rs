@@ -0,0 +1,178 @@
+//! Offline tests of wallet reconciliation outcomes and Core's wallet errors
+//! against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use capstone::wallet::{self, Reconciled, WalletError, WalletSpec};
+use capstone::Error;
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::json;
+
+// Core's RPC_WALLET_ERROR, RPC_WALLET_ALREADY_LOADED and RPC_WALLET_ALREADY_EXISTS.
+const WALLET_ERROR: i32 = -4;
+const WALLET_ALREADY_LOADED: i32 = -35;
+const WALLET_ALREADY_EXISTS: i32 = -36;
+
+fn reconcile_miner(mock: &MockRpc, spec: Option<&WalletSpec>) -> capstone::Result<Reconciled> {
+    let config = mock.config();
+    let credentials = mock.credentials();
+    let rpc = credentials.connect(&config.rpc_url())?;
+    let (_, outcome) = wallet::reconcile(&rpc, &config, &credentials, "Miner", spec, None)?;
+    Ok(outcome)
+}
+
+#[test]
+fn reconcile_reports_each_outcome_and_is_idempotent() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir_empty")
+        .reply("createwallet", "createwallet");
+    assert_eq!(reconcile_miner(&mock, None).unwrap(), Reconciled::Created);
+
+    // The node after the first run
+    mock.reply("listwallets", "listwallets_miner");
+    assert_eq!(
+        reconcile_miner(&mock, None).unwrap(),
+        Reconciled::AlreadyLoaded
+    );
+    assert_eq!(
+        mock.methods(),
+        [
+            "listwallets",
+            "listwalletdir",
+            "createwallet",
+            "listwallets"
+        ]
+    );
+
+    // After a node restart
+    mock.reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir")
+        .reply("loadwallet", "loadwallet");
+    assert_eq!(reconcile_miner(&mock, None).unwrap(), Reconciled::Loaded);
+    assert_eq!(Reconciled::Loaded.to_string(), "loaded");
+}
+
+#[test]
+fn reconcile_loads_wallet_another_client_created_first() {
+    for (code, message) in [
+        (
+            WALLET_ALREADY_EXISTS,
+            "Failed to create database path '/data/regtest/wallets/Miner'. Database already exists.",
+        ),
+        // Core before 25
+        (
+            WALLET_ERROR,
+            "Wallet file verification failed. Failed to create database path '/data/regtest/wallets/Miner'. Database already exists.",
+        ),
+    ] {
+        let mock = MockRpc::start();
+        mock.reply("listwallets", "listwallets_empty")
+            .reply("listwalletdir", "listwalletdir_empty")
+            .fail("createwallet", code, message)
+            .reply("loadwallet", "loadwallet");
+
+        assert_eq!(reconcile_miner(&mock, None).unwrap(), Reconciled::Loaded);
+        assert_eq!(mock.methods().last().unwrap(), "loadwallet");
+    }
+}
+
+#[test]
+fn reconcile_takes_wallet_another_client_loaded_first() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir")
+        .fail(
+            "loadwallet",
+            WALLET_ALREADY_LOADED,
+            "Wallet \"Miner\" is already loaded.",
+        );
+
+    assert_eq!(
+        reconcile_miner(&mock, None).unwrap(),
+        Reconciled::AlreadyLoaded
+    );
+}
+
+#[test]
+fn reconcile_diagnoses_wallet_loaded_by_path() {
+    let mock = MockRpc::start();
+    mock.reply_value("listwallets", json!(["/data/regtest/wallets/Miner"]))
+        .reply("listwalletdir", "listwalletdir")
+        .fail(
+            "loadwallet",
+            WALLET_ERROR,
+            "Wallet file verification failed. SQLiteDatabase: Unable to obtain an exclusive lock on the database, is it being used by another instance of Bitcoin Core?",
+        );
+
+    let e = reconcile_miner(&mock, None).unwrap_err();
+
+    match &e {
+        Error::Wallet(WalletError::LoadedElsewhere {
+            wallet, loaded_as, ..
+        }) => {
+            assert_eq!(wallet, "Miner");
+            assert_eq!(loaded_as, "/data/regtest/wallets/Miner");
+        }
+        e => panic!("expected LoadedElsewhere, got {e:?}"),
+    }
+    assert!(e.to_string().contains("exclusive lock"));
+    assert_eq!(e.exit_code(), 6);
+}
+
+#[test]
+fn reconcile_reports_corrupt_wallet() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir")
+        .fail(
+            "loadwallet",
+            WALLET_ERROR,
+            "Wallet file verification failed. wallet.dat corrupt, salvage failed",
+        );
+
+    let e = reconcile_miner(&mock, None).unwrap_err();
+
+    assert!(
+        matches!(&e, Error::Wallet(WalletError::Unloadable { message, .. }) if message.contains("corrupt")),
+        "{e:?}"
+    );
+}
+
+#[test]
+fn reconcile_returns_mismatch_as_outcome() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets")
+        .reply("getwalletinfo", "getwalletinfo");
+
+    let outcome = reconcile_miner(&mock, Some(&WalletSpec::watch_only())).unwrap();
+
+    assert!(matches!(&outcome, Reconciled::Mismatch(m) if m.len() == 1));
+    assert_eq!(
+        outcome.to_string(),
+        "loaded, but disable_private_keys is false (expected true)"
+    );
+    let e = outcome.matched("Miner").unwrap_err();
+    assert!(matches!(e, Error::WalletMismatch { .. }));
+}
+
+#[test]
+fn reconcile_reports_blank_wallet_expected_to_have_keys() {
+    let mock = MockRpc::start();
+    let mut info = load_fixture("getwalletinfo");
+    info["blank"] = true.into();
+    mock.reply("listwallets", "listwallets")
+        .reply_value("getwalletinfo", info);
+
+    let outcome = reconcile_miner(&mock, Some(&WalletSpec::new())).unwrap();
+
+    assert_eq!(
+        outcome.to_string(),
+        "loaded, but blank is true (expected false)"
+    );
+
+    // Core clears the flag when keys are imported into a wallet created blank
+    mock.reply("getwalletinfo", "getwalletinfo");
+    let outcome = reconcile_miner(&mock, Some(&WalletSpec::new().blank(true))).unwrap();
+    assert_eq!(outcome, Reconciled::AlreadyLoaded);
+}
@@ -0,0 +1,353 @@
+//! Tests of the report output formats, on a report built by hand, and of
+//! extracting a report against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::absolute::LockTime;
+use bitcoincore_rpc::bitcoin::consensus::encode::serialize_hex;
+use bitcoincore_rpc::bitcoin::transaction::Version;
+use bitcoincore_rpc::bitcoin::{
+    Address, Amount, BlockHash, Network, OutPoint, ScriptBuf, Sequence, SignedAmount, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use capstone::report::{OutputKind, ReportFormat, ReportInput, ReportOutput};
+use capstone::TransactionReport;
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+const TXID: &str = "6c2fae5e0e51422beb17fde022dca96279827a9f20852e8179f6d6dadaef9505";
+const MINER_ADDRESS: &str = "bcrt1qqszqgpqyqszqgpqyqszqgpqyqszqgpqyuza2rq";
+const TRADER_ADDRESS: &str = "bcrt1qqyqszqgpqyqszqgpqyqszqgpqyqszqgpvxat9t";
+const CHANGE_ADDRESS: &str = "bcrt1qqgpqyqszqgpqyqszqgpqyqszqgpqyqszazmwwa";
+
+fn address(address: &str) -> Option<Address> {
+    Some(
+        Address::from_str(address)
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap(),
+    )
+}
+
+fn output(vout: u32, to: &str, btc: f64, kind: OutputKind, label: &str) -> ReportOutput {
+    ReportOutput {
+        vout,
+        address: address(to),
+        amount: Amount::from_btc(btc).unwrap(),
+        script_type: "witness_v0_keyhash",
+        kind,
+        wallet: Some("Miner".to_owned()),
+        label: Some(label.to_owned()),
+    }
+}
+
+fn report() -> TransactionReport {
+    let txid = Txid::from_str(TXID).unwrap();
+    TransactionReport {
+        txid,
+        inputs: vec![ReportInput {
+            outpoint: OutPoint::new(txid, 0),
+            address: address(MINER_ADDRESS),
+            amount: Amount::from_btc(50.0).unwrap(),
+            script_type: "witness_v0_keyhash",
+            wallet: Some("Miner".to_owned()),
+            label: Some("Mining Reward".to_owned()),
+        }],
+        outputs: vec![
+            output(
+                0,
+                TRADER_ADDRESS,
+                20.0,
+                OutputKind::Recipient,
+                "rent | march",
+            ),
+            output(1, CHANGE_ADDRESS, 29.9999859, OutputKind::Change, "change"),
+        ],
+        fee: SignedAmount::from_sat(-1410),
+        block_height: 102,
+        block_hash: BlockHash::from_str(
+            "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        )
+        .unwrap(),
+    }
+}
+
+fn write(report: &TransactionReport, format: ReportFormat) -> String {
+    let mut out = Vec::new();
+    report.write(format, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn json_uses_snake_case_kinds_and_btc_amounts() {
+    let json: Value = serde_json::from_str(&write(&report(), ReportFormat::Json)).unwrap();
+
+    assert_eq!(json["txid"], TXID);
+    assert_eq!(json["fee"], json!(-0.0000141));
+    assert_eq!(json["outputs"][0]["kind"], "recipient");
+    assert_eq!(json["outputs"][0]["label"], "rent | march");
+    assert_eq!(json["outputs"][1]["amount"], json!(29.9999859));
+}
+
+#[test]
+fn csv_has_a_header_and_one_row() {
+    let csv = write(&report(), ReportFormat::Csv);
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("txid,input_addresses,"), "{csv}");
+    assert!(lines[1].starts_with(TXID), "{csv}");
+    assert!(
+        lines[1].ends_with(",Mining Reward,rent | march,change"),
+        "{csv}"
+    );
+}
+
+#[test]
+fn markdown_escapes_pipes_and_names_kinds_like_json() {
+    let markdown = write(&report(), ReportFormat::Markdown);
+
+    assert!(
+        markdown.contains("| recipient_labels | rent \\| march |"),
+        "{markdown}"
+    );
+    assert!(
+        markdown.contains(&format!(
+            "| 0 | {TRADER_ADDRESS} | rent \\| march | 20 | witness_v0_keyhash | recipient | Miner |"
+        )),
+        "{markdown}"
+    );
+    assert!(markdown.contains("| change | Miner |"), "{markdown}");
+    // Every table row has as many cells as its header
+    for line in markdown.lines().filter(|l| l.starts_with('|')) {
+        let cells = line.replace("\\|", "").matches('|').count() - 1;
+        assert!([2, 6, 7].contains(&cells), "{line}");
+    }
+}
+
+fn transaction(inputs: &[OutPoint], outputs: Vec<(ScriptBuf, Amount)>) -> Transaction {
+    Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|&previous_output| TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs
+            .into_iter()
+            .map(|(script_pubkey, value)| TxOut {
+                value,
+                script_pubkey,
+            })
+            .collect(),
+    }
+}
+
+fn script(address: &str) -> ScriptBuf {
+    Address::from_str(address)
+        .unwrap()
+        .assume_checked()
+        .script_pubkey()
+}
+
+fn btc(btc: f64) -> Amount {
+    Amount::from_btc(btc).unwrap()
+}
+
+// The Trader address that comes back as Trader's change in the payment below.
+fn trader_change() -> Address {
+    Address::p2wsh(&ScriptBuf::from_bytes(vec![0x51]), Network::Regtest)
+}
+
+// A payment spending a Miner and a Trader output, paying Trader, change to both
+// wallets, an OP_RETURN and a bare OP_TRUE output. Each wallet owns only its
+// own addresses; returns the payment's txid.
+fn mock_payment(mock: &MockRpc) -> Txid {
+    let funding = transaction(
+        &[OutPoint::null()],
+        vec![(script(MINER_ADDRESS), btc(50.0))],
+    );
+    let received = transaction(
+        &[OutPoint::null()],
+        vec![
+            (script(CHANGE_ADDRESS), btc(1.0)),
+            (script(TRADER_ADDRESS), btc(10.0)),
+        ],
+    );
+    let payment = transaction(
+        &[
+            OutPoint::new(funding.txid(), 0),
+            OutPoint::new(received.txid(), 1),
+        ],
+        vec![
+            (script(TRADER_ADDRESS), btc(40.0)),
+            (script(CHANGE_ADDRESS), btc(19.9998)),
+            (trader_change().script_pubkey(), btc(0.0001)),
+            (
+                ScriptBuf::from_bytes(vec![0x6a, 0x02, 0xca, 0xfe]),
+                Amount::ZERO,
+            ),
+            (ScriptBuf::from_bytes(vec![0x51]), btc(0.00005)),
+        ],
+    );
+
+    let mut details = load_fixture("gettransaction_unconfirmed");
+    details["txid"] = payment.txid().to_string().into();
+    details["hex"] = serialize_hex(&payment).into();
+    details["fee"] = json!(-0.00005);
+    details["confirmations"] = 1.into();
+    details["blockhash"] = load_fixture("getblock")["hash"].clone();
+    details["blockheight"] = 102.into();
+
+    let mine = |change: bool, label: &str| json!({ "ismine": true, "ischange": change, "labels": [label] });
+    mock.reply("listwallets", "listwallets")
+        .reply("getblock", "getblock")
+        .reply_value("gettransaction", details)
+        .reply_value_for(
+            "getrawtransaction",
+            funding.txid().to_string(),
+            serialize_hex(&funding).into(),
+        )
+        .reply_value_for(
+            "getrawtransaction",
+            received.txid().to_string(),
+            serialize_hex(&received).into(),
+        )
+        .reply_value("getaddressinfo", json!({ "ismine": false }))
+        .reply_value_on_for(
+            "Miner",
+            "getaddressinfo",
+            MINER_ADDRESS,
+            mine(false, "Mining Reward"),
+        )
+        .reply_value_on_for("Miner", "getaddressinfo", CHANGE_ADDRESS, mine(true, ""))
+        .reply_value_on_for(
+            "Trader",
+            "getaddressinfo",
+            TRADER_ADDRESS,
+            mine(false, "Received"),
+        )
+        .reply_value_on_for(
+            "Trader",
+            "getaddressinfo",
+            trader_change().to_string(),
+            mine(true, ""),
+        );
+    payment.txid()
+}
+
+#[test]
+fn extract_resolves_inputs_and_classifies_outputs() {
+    let mock = MockRpc::start();
+    let txid = mock_payment(&mock);
+    let node = mock.node();
+    let miner = node.loaded_wallet("Miner").unwrap();
+
+    let report = TransactionReport::extract(&node, &miner, &txid).unwrap();
+
+    assert_eq!(report.txid, txid);
+    assert_eq!(report.block_height, 102);
+    assert_eq!(report.fee, SignedAmount::from_sat(-5000));
+    // Each input is resolved through its parent and found in the wallet that owns it
+    let inputs: Vec<_> = report
+        .inputs
+        .iter()
+        .map(|i| {
+            (
+                i.address.clone(),
+                i.amount,
+                i.wallet.as_deref(),
+                i.label.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        inputs,
+        [
+            (
+                address(MINER_ADDRESS),
+                btc(50.0),
+                Some("Miner"),
+                Some("Mining Reward")
+            ),
+            (
+                address(TRADER_ADDRESS),
+                btc(10.0),
+                Some("Trader"),
+                Some("Received")
+            ),
+        ]
+    );
+    assert_eq!(report.inputs[1].outpoint.vout, 1);
+    assert_eq!(report.input_amount(), btc(60.0));
+    assert_eq!(report.input_labels(), "Mining Reward,Received");
+
+    let outputs: Vec<_> = report
+        .outputs
+        .iter()
+        .map(|o| (o.kind, o.wallet.as_deref(), o.script_type))
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            (OutputKind::Recipient, Some("Trader"), "witness_v0_keyhash"),
+            (OutputKind::Change, Some("Miner"), "witness_v0_keyhash"),
+            // Change, but of a wallet other than the sender's
+            (
+                OutputKind::Recipient,
+                Some("Trader"),
+                "witness_v0_scripthash"
+            ),
+            (OutputKind::Data, None, "nulldata"),
+            (OutputKind::NonStandard, None, "nonstandard"),
+        ]
+    );
+    assert_eq!(report.outputs[0].label.as_deref(), Some("Received"));
+    // Unlabelled addresses have no label rather than an empty one
+    assert_eq!(report.outputs[1].label, None);
+    assert_eq!(report.output_amount(OutputKind::Recipient), btc(40.0001));
+    assert_eq!(report.output_amount(OutputKind::Change), btc(19.9998));
+    // Inputs pay for every output and the fee
+    let spent: Amount = report.outputs.iter().map(|o| o.amount).sum();
+    assert_eq!(
+        report.input_amount() - spent,
+        report.fee.abs().to_unsigned().unwrap()
+    );
+}
+
+#[test]
+fn extract_counts_change_only_for_the_sender() {
+    let mock = MockRpc::start();
+    let txid = mock_payment(&mock);
+    let node = mock.node();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    let report = TransactionReport::extract(&node, &trader, &txid).unwrap();
+
+    let kinds: Vec<OutputKind> = report.outputs.iter().map(|o| o.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            OutputKind::Recipient,
+            OutputKind::Recipient,
+            OutputKind::Change,
+            OutputKind::Data,
+            OutputKind::NonStandard,
+        ]
+    );
+    // The sender's wallet is asked first
+    let lookups: Vec<String> = mock
+        .calls()
+        .into_iter()
+        .filter(|c| c.method == "getaddressinfo")
+        .map(|c| c.path)
+        .take(1)
+        .collect();
+    assert_eq!(lookups, ["/wallet/Trader"]);
+}
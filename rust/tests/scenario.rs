@@ -0,0 +1,202 @@
+//! Offline tests of scenario files and the step runner against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use capstone::scenario::{Outcome, Scenario, Step};
+use capstone::{Error, Node};
+use mock_rpc::MockRpc;
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+fn write_scenario(name: &str, toml: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("capstone-scenario-{name}-{}.toml", process::id()));
+    fs::write(&path, toml).unwrap();
+    path
+}
+
+fn scenario(toml: &str) -> Scenario {
+    toml::from_str(toml).unwrap()
+}
+
+#[test]
+fn scenario_file_parses_steps_with_defaults() {
+    let path = write_scenario(
+        "parse",
+        r#"
+name = "Miner pays Trader"
+
+[[step]]
+action = "send"
+from = "Miner"
+to = "Trader"
+amount = 20.0
+id = "payment"
+
+[[step]]
+action = "assert-balance"
+wallet = "Trader"
+min = 19.5
+
+[[step]]
+action = "wait-for-mempool"
+tx = "payment"
+"#,
+    );
+
+    let scenario = Scenario::from_file(&path);
+    fs::remove_file(&path).unwrap();
+
+    let scenario = scenario.unwrap();
+    assert_eq!(scenario.name.as_deref(), Some("Miner pays Trader"));
+    let steps: Vec<String> = scenario.steps.iter().map(Step::to_string).collect();
+    assert_eq!(
+        steps,
+        [
+            "send 20 BTC from Miner to Trader",
+            "assert-balance Trader",
+            "wait-for-mempool payment"
+        ]
+    );
+    assert!(matches!(
+        &scenario.steps[1],
+        Step::AssertBalance {
+            equals: None,
+            include_pending: false,
+            ..
+        }
+    ));
+    assert!(matches!(
+        scenario.steps[2],
+        Step::WaitForMempool {
+            timeout_secs: 30,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn scenario_file_rejects_unknown_actions_and_fields() {
+    for (name, toml) in [
+        (
+            "action",
+            "[[step]]\naction = \"teleport\"\nwallet = \"Miner\"\n",
+        ),
+        (
+            "field",
+            "[[step]]\naction = \"create-wallet\"\nwallet = \"Miner\"\ncolor = \"red\"\n",
+        ),
+    ] {
+        let path = write_scenario(name, toml);
+        let e = Error::from(Scenario::from_file(&path).unwrap_err());
+        fs::remove_file(&path).unwrap();
+
+        assert!(e.to_string().starts_with("invalid scenario"), "{e}");
+        assert_eq!(e.exit_code(), 2);
+    }
+}
+
+#[test]
+fn run_stops_at_the_first_failed_step() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply("listwallets", "listwallets")
+        .reply("getbalances", "getbalances");
+    let node = Node::connect(mock.config()).unwrap();
+
+    let report = scenario(
+        r#"
+[[step]]
+action = "create-wallet"
+wallet = "Miner"
+
+[[step]]
+action = "assert-balance"
+wallet = "Miner"
+equals = 20.0
+
+[[step]]
+action = "assert-balance"
+wallet = "Miner"
+min = 25.0
+max = 50.0
+
+[[step]]
+action = "mine"
+blocks = 1
+to = "Miner"
+"#,
+    )
+    .run(&node);
+
+    let outcomes: Vec<&Outcome> = report.steps.iter().map(|s| &s.outcome).collect();
+    assert_eq!(
+        outcomes,
+        [
+            &Outcome::Passed("Miner loaded".to_owned()),
+            &Outcome::Passed("balance 20 BTC".to_owned()),
+            &Outcome::Failed(
+                "balance 20 BTC outside bounds (equals -, min 25 BTC, max 50 BTC)".to_owned()
+            ),
+            &Outcome::Skipped,
+        ]
+    );
+    assert!(!report.passed());
+    assert!(report.to_string().ends_with("Scenario failed"));
+    // The skipped step never reached the node
+    assert!(!mock.methods().contains(&"getnewaddress".to_owned()));
+}
+
+#[test]
+fn assert_balance_counts_pending_only_when_asked() {
+    let mock = MockRpc::start();
+    let mut balances = mock_rpc::load_fixture("getbalances_empty");
+    balances["mine"]["untrusted_pending"] = 1.0.into();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply("listwallets", "listwallets")
+        .reply_value("getbalances", balances);
+    let node = Node::connect(mock.config()).unwrap();
+
+    let report = scenario(
+        r#"
+[[step]]
+action = "assert-balance"
+wallet = "Trader"
+equals = 1.0
+include_pending = true
+
+[[step]]
+action = "assert-balance"
+wallet = "Trader"
+min = 0.5
+"#,
+    )
+    .run(&node);
+
+    assert_eq!(
+        report.steps[0].outcome,
+        Outcome::Passed("balance 1 BTC".to_owned())
+    );
+    assert!(matches!(report.steps[1].outcome, Outcome::Failed(_)));
+}
+
+#[test]
+fn wait_for_mempool_fails_on_a_timeout_past_the_clock() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo");
+    let node = Node::connect(mock.config()).unwrap();
+
+    let report = scenario(
+        r#"
+[[step]]
+action = "wait-for-mempool"
+timeout_secs = 9223372036854775807
+"#,
+    )
+    .run(&node);
+
+    assert_eq!(
+        report.steps[0].outcome,
+        Outcome::Failed("timeout_secs 9223372036854775807 is too large".to_owned())
+    );
+    assert!(!report.passed());
+}
@@ -0,0 +1,481 @@
+//! Offline tests of wallet setup and sending against the mock JSON-RPC server.
+
+mod mock_rpc;
+
+use bitcoincore_rpc::bitcoin::{Address, Amount, Network, Txid};
+use capstone::send::{EstimateMode, SendOptions, SendResult};
+use capstone::wallet::{self, SpecMismatch, WalletError, WalletSpec};
+use capstone::{Error, Node, TransactionReport};
+use mock_rpc::{load_fixture, MockRpc};
+use serde_json::json;
+use std::str::FromStr;
+
+const TXID: &str = "6c2fae5e0e51422beb17fde022dca96279827a9f20852e8179f6d6dadaef9505";
+const TRADER_ADDRESS: &str = "bcrt1qqyqszqgpqyqszqgpqyqszqgpqyqszqgpvxat9t";
+const CHANGE_ADDRESS: &str = "bcrt1qqgpqyqszqgpqyqszqgpqyqszqgpqyqszazmwwa";
+
+// Core's RPC_WALLET_ERROR, RPC_WALLET_NOT_FOUND, RPC_WALLET_INSUFFICIENT_FUNDS
+// and RPC_WALLET_INVALID_LABEL_NAME.
+const WALLET_ERROR: i32 = -4;
+const WALLET_NOT_FOUND: i32 = -18;
+const INSUFFICIENT_FUNDS: i32 = -6;
+const INVALID_LABEL_NAME: i32 = -11;
+
+fn ensure_miner(mock: &MockRpc) -> capstone::Result<bitcoincore_rpc::Client> {
+    ensure_miner_as(mock, &WalletSpec::new())
+}
+
+fn ensure_miner_as(mock: &MockRpc, spec: &WalletSpec) -> capstone::Result<bitcoincore_rpc::Client> {
+    let config = mock.config();
+    let credentials = mock.credentials();
+    let rpc = credentials.connect(&config.rpc_url())?;
+    wallet::ensure_wallet(&rpc, &config, &credentials, "Miner", spec)
+}
+
+fn address(address: &str) -> Address {
+    Address::from_str(address)
+        .unwrap()
+        .require_network(Network::Regtest)
+        .unwrap()
+}
+
+fn btc(btc: f64) -> Amount {
+    Amount::from_btc(btc).unwrap()
+}
+
+fn rpc_error_code(e: &Error) -> Option<i32> {
+    match e {
+        Error::Rpc(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Rpc(e))) => {
+            Some(e.code)
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn ensure_wallet_creates_missing_wallet() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir_empty")
+        .reply("createwallet", "createwallet");
+
+    ensure_miner(&mock).unwrap();
+
+    assert_eq!(
+        mock.methods(),
+        ["listwallets", "listwalletdir", "createwallet"]
+    );
+    assert_eq!(mock.calls()[2].params[0], "Miner");
+}
+
+#[test]
+fn ensure_wallet_loads_unloaded_wallet() {
+    let mock = MockRpc::start();
+    mock.reply("listwalletdir", "listwalletdir")
+        .reply("listwallets", "listwallets_empty")
+        .reply("loadwallet", "loadwallet")
+        .reply("getwalletinfo", "getwalletinfo");
+
+    ensure_miner(&mock).unwrap();
+
+    assert_eq!(
+        mock.methods(),
+        [
+            "listwallets",
+            "listwalletdir",
+            "loadwallet",
+            "getwalletinfo"
+        ]
+    );
+    assert_eq!(mock.calls()[2].params, json!(["Miner"]));
+    assert_eq!(mock.calls()[3].path, "/wallet/Miner");
+}
+
+#[test]
+fn ensure_wallet_reuses_loaded_wallet() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets")
+        .reply("getwalletinfo", "getwalletinfo");
+
+    ensure_miner(&mock).unwrap();
+
+    assert_eq!(mock.methods(), ["listwallets", "getwalletinfo"]);
+}
+
+#[test]
+fn ensure_wallet_creates_watch_only_wallet() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir_empty")
+        .reply("createwallet", "createwallet");
+
+    ensure_miner_as(&mock, &WalletSpec::watch_only().load_on_startup(true)).unwrap();
+
+    // name, disable_private_keys, blank, passphrase, avoid_reuse, descriptors, load_on_startup
+    assert_eq!(
+        mock.calls()[2].params,
+        json!(["Miner", true, false, null, false, true, true])
+    );
+    // A wallet just created as requested isn't checked again
+    assert_eq!(
+        mock.methods(),
+        ["listwallets", "listwalletdir", "createwallet"]
+    );
+}
+
+#[test]
+fn ensure_wallet_passes_load_on_startup_when_loading() {
+    let mock = MockRpc::start();
+    mock.reply("listwalletdir", "listwalletdir")
+        .reply("listwallets", "listwallets_empty")
+        .reply("loadwallet", "loadwallet")
+        .reply("getwalletinfo", "getwalletinfo");
+
+    ensure_miner_as(&mock, &WalletSpec::new().load_on_startup(false)).unwrap();
+
+    assert_eq!(mock.calls()[2].params, json!(["Miner", false]));
+}
+
+#[test]
+fn ensure_wallet_rejects_wallet_set_up_differently() {
+    let mock = MockRpc::start();
+    let mut info = load_fixture("getwalletinfo");
+    info["blank"] = true.into();
+    mock.reply("listwallets", "listwallets")
+        .reply_value("getwalletinfo", info);
+
+    let e = ensure_miner_as(&mock, &WalletSpec::watch_only().avoid_reuse(true)).unwrap_err();
+
+    match &e {
+        Error::WalletMismatch { wallet, mismatches } => {
+            assert_eq!(wallet, "Miner");
+            assert_eq!(
+                mismatches[..],
+                [
+                    SpecMismatch {
+                        setting: "disable_private_keys",
+                        expected: true,
+                        actual: false,
+                    },
+                    SpecMismatch {
+                        setting: "avoid_reuse",
+                        expected: true,
+                        actual: false,
+                    },
+                    SpecMismatch {
+                        setting: "blank",
+                        expected: false,
+                        actual: true,
+                    },
+                ]
+            );
+        }
+        e => panic!("expected a mismatch, got {e}"),
+    }
+    assert_eq!(e.exit_code(), 6);
+
+    // A wallet created blank may have had keys imported since
+    let mut info = load_fixture("getwalletinfo");
+    info["private_keys_enabled"] = false.into();
+    mock.reply_value("getwalletinfo", info);
+    ensure_miner_as(&mock, &WalletSpec::watch_only().blank(true)).unwrap();
+}
+
+#[test]
+fn ensure_wallet_reports_create_failure() {
+    let mock = MockRpc::start();
+    mock.reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir_empty")
+        .fail(
+            "createwallet",
+            WALLET_ERROR,
+            "Compiled without sqlite support (required for descriptor wallets)",
+        );
+
+    let e = ensure_miner(&mock).unwrap_err();
+
+    assert_eq!(rpc_error_code(&e), Some(WALLET_ERROR));
+    assert_eq!(
+        mock.methods(),
+        ["listwallets", "listwalletdir", "createwallet"]
+    );
+}
+
+#[test]
+fn ensure_wallet_reports_load_failure() {
+    let mock = MockRpc::start();
+    mock.reply("listwalletdir", "listwalletdir")
+        .reply("listwallets", "listwallets_empty")
+        .fail(
+            "loadwallet",
+            WALLET_NOT_FOUND,
+            "Wallet file verification failed. Failed to load database path '/data/regtest/wallets/Miner'. Path does not exist.",
+        );
+
+    let e = ensure_miner(&mock).unwrap_err();
+
+    assert!(
+        matches!(&e, Error::Wallet(WalletError::NotFound { wallet, message })
+            if wallet == "Miner" && message.ends_with("Path does not exist.")),
+        "{e:?}"
+    );
+    assert_eq!(e.exit_code(), 6);
+}
+
+#[test]
+fn node_ensure_wallet_binds_wallet_endpoint() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply("listwalletdir", "listwalletdir")
+        .reply("listwallets", "listwallets")
+        .reply("send", "send");
+
+    let node = Node::connect(mock.config()).unwrap();
+    let miner = node.ensure_wallet("Miner").unwrap();
+    miner
+        .send(&[(address(TRADER_ADDRESS), btc(20.0))], &SendOptions::new())
+        .unwrap();
+
+    let calls = mock.calls();
+    assert_eq!(calls[0].path, "/");
+    let send = calls.last().unwrap();
+    assert_eq!(send.method, "send");
+    assert_eq!(send.path, "/wallet/Miner");
+}
+
+#[test]
+fn node_load_wallet_never_creates_wallet() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply("listwallets", "listwallets_empty")
+        .reply("listwalletdir", "listwalletdir_empty");
+    let node = Node::connect(mock.config()).unwrap();
+
+    let e = node.load_wallet("Miner").err().unwrap();
+
+    assert!(
+        matches!(&e, Error::WalletNotFound(name) if name == "Miner"),
+        "{e:?}"
+    );
+    assert_eq!(e.exit_code(), 6);
+    assert!(!mock.methods().contains(&"createwallet".to_owned()));
+
+    // Loaded by path, so only in listwallets
+    mock.reply_value("listwallets", json!(["Miner"]));
+    let (miner, outcome) = node.load_wallet("Miner").unwrap();
+    assert_eq!(miner.name(), "Miner");
+    assert_eq!(outcome, wallet::Reconciled::AlreadyLoaded);
+}
+
+#[test]
+fn labels_and_their_addresses() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply_value("listlabels", json!(["", "Mining Reward", "Received"]))
+        .reply_value(
+            "getaddressesbylabel",
+            json!({
+                TRADER_ADDRESS: { "purpose": "receive" },
+                CHANGE_ADDRESS: { "purpose": "receive" },
+            }),
+        );
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    assert_eq!(trader.labels().unwrap(), ["", "Mining Reward", "Received"]);
+    // Sorted, so listings are stable
+    assert_eq!(
+        trader.addresses_by_label("Received").unwrap(),
+        [address(CHANGE_ADDRESS), address(TRADER_ADDRESS)]
+    );
+    let call = mock.calls().pop().unwrap();
+    assert_eq!(call.path, "/wallet/Trader");
+    assert_eq!(call.params, json!(["Received"]));
+}
+
+#[test]
+fn addresses_by_unused_label_are_empty() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo").fail(
+        "getaddressesbylabel",
+        INVALID_LABEL_NAME,
+        "No addresses with label Received",
+    );
+    let node = Node::connect(mock.config()).unwrap();
+    let trader = node.loaded_wallet("Trader").unwrap();
+
+    assert!(trader.addresses_by_label("Received").unwrap().is_empty());
+
+    // Other failures are still errors
+    mock.fail(
+        "getaddressesbylabel",
+        WALLET_NOT_FOUND,
+        "Requested wallet does not exist",
+    );
+    let e = trader.addresses_by_label("Received").unwrap_err();
+    assert_eq!(rpc_error_code(&e), Some(WALLET_NOT_FOUND));
+}
+
+#[test]
+fn send_returns_txid() {
+    let mock = MockRpc::start();
+    mock.reply("send", "send");
+    let rpc = mock
+        .credentials()
+        .connect(&mock.config().rpc_url())
+        .unwrap();
+
+    let result = wallet::send(
+        &rpc,
+        &[(address(TRADER_ADDRESS), btc(20.0))],
+        &SendOptions::new(),
+    )
+    .unwrap();
+
+    assert_eq!(result, SendResult::Complete(Txid::from_str(TXID).unwrap()));
+    assert_eq!(
+        mock.calls()[0].params,
+        json!([[{ TRADER_ADDRESS: 20.0 }], null, null, null, {}])
+    );
+}
+
+#[test]
+fn send_pays_several_recipients_with_options() {
+    let mock = MockRpc::start();
+    mock.reply("send", "send");
+    let rpc = mock
+        .credentials()
+        .connect(&mock.config().rpc_url())
+        .unwrap();
+    let recipients = [
+        (address(TRADER_ADDRESS), btc(20.0)),
+        (address(CHANGE_ADDRESS), btc(1.5)),
+    ];
+    let options = SendOptions::new()
+        .fee_rate(3.0)
+        .subtract_fee(true)
+        .add_inputs(true)
+        .change_address(address(CHANGE_ADDRESS))
+        .change_position(1)
+        .include_watching(false)
+        .locktime(101)
+        .replaceable(true);
+
+    wallet::send(&rpc, &recipients, &options).unwrap();
+
+    assert_eq!(
+        mock.calls()[0].params,
+        json!([
+            [{ TRADER_ADDRESS: 20.0 }, { CHANGE_ADDRESS: 1.5 }],
+            null,
+            null,
+            3.0,
+            {
+                "subtract_fee_from_outputs": [0, 1],
+                "add_inputs": true,
+                "change_address": CHANGE_ADDRESS,
+                "change_position": 1,
+                "include_watching": false,
+                "locktime": 101,
+                "replaceable": true
+            }
+        ])
+    );
+}
+
+#[test]
+fn send_reports_rpc_error() {
+    let mock = MockRpc::start();
+    mock.fail("send", INSUFFICIENT_FUNDS, "Insufficient funds");
+    let rpc = mock
+        .credentials()
+        .connect(&mock.config().rpc_url())
+        .unwrap();
+
+    let e = wallet::send(
+        &rpc,
+        &[(address(TRADER_ADDRESS), btc(20.0))],
+        &SendOptions::new(),
+    )
+    .unwrap_err();
+
+    assert_eq!(rpc_error_code(&e), Some(INSUFFICIENT_FUNDS), "{e:?}");
+}
+
+#[test]
+fn send_to_address_passes_fee_options() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply("sendtoaddress", "sendtoaddress")
+        .reply("gettransaction", "gettransaction_unconfirmed");
+    let node = Node::connect(mock.config()).unwrap();
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let address = address(TRADER_ADDRESS);
+    let options = SendOptions::new()
+        .conf_target(6)
+        .estimate_mode(EstimateMode::Conservative)
+        .subtract_fee(true);
+
+    let sent = miner
+        .send_to_address(&address, btc(20.0), &options)
+        .unwrap();
+
+    let params = &mock.calls()[1].params;
+    assert_eq!(
+        params,
+        &json!([
+            TRADER_ADDRESS,
+            20.0,
+            null,
+            null,
+            true,
+            null,
+            6,
+            "conservative",
+            null,
+            null
+        ])
+    );
+    assert_eq!(sent.txid, Txid::from_str(TXID).unwrap());
+    assert_eq!(sent.fee, Amount::from_sat(1410));
+    assert_eq!(sent.vsize, 113);
+    assert!((sent.fee_rate() - 1410.0 / 113.0).abs() < 1e-9);
+}
+
+#[test]
+fn send_returns_psbt_when_incomplete() {
+    let mock = MockRpc::start();
+    mock.reply("send", "send_incomplete");
+    let rpc = mock
+        .credentials()
+        .connect(&mock.config().rpc_url())
+        .unwrap();
+
+    let result = wallet::send(
+        &rpc,
+        &[(address(TRADER_ADDRESS), btc(20.0))],
+        &SendOptions::new(),
+    )
+    .unwrap();
+
+    let psbt = load_fixture("send_incomplete")["psbt"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    assert_eq!(result, SendResult::Incomplete(psbt));
+}
+
+#[test]
+fn report_rejects_unconfirmed_transaction() {
+    let mock = MockRpc::start();
+    mock.reply("getblockchaininfo", "getblockchaininfo")
+        .reply("gettransaction", "gettransaction_unconfirmed");
+    let node = Node::connect(mock.config()).unwrap();
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let txid = Txid::from_str(TXID).unwrap();
+
+    let e = TransactionReport::extract(&node, &miner, &txid).unwrap_err();
+
+    assert!(matches!(e, Error::TxUnconfirmed(t) if t == txid), "{e:?}");
+    assert_eq!(e.exit_code(), 6);
+}
@@ -0,0 +1,64 @@
+//! Runs the Miner -> Trader flow against a throwaway regtest node and checks
+//! the same invariants as `test/test.spec.ts`.
+
+mod common;
+
+use bitcoincore_rpc::bitcoin::{Amount, SignedAmount};
+use bitcoincore_rpc::RpcApi;
+use capstone::report::OutputKind;
+use capstone::workflow;
+use common::Bitcoind;
+
+#[test]
+#[ignore = "needs bitcoind; run with `cargo test -- --ignored`"]
+fn miner_pays_trader() {
+    let bitcoind = Bitcoind::spawn();
+    let node = bitcoind.connect();
+
+    let report = workflow::run(&node).expect("workflow run");
+
+    // out.txt: ten lines, 64-character txid and block hash
+    let mut out = Vec::new();
+    report.write_text(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.trim().split('\n').collect();
+    assert_eq!(lines.len(), 10, "out.txt:\n{out}");
+    assert_eq!(lines[0].len(), 64);
+    assert_eq!(lines[9].len(), 64);
+
+    // One coinbase input pays the Trader and change back to the Miner
+    assert_eq!(report.inputs.len(), 1);
+    assert_eq!(report.outputs.len(), 2);
+    assert_eq!(
+        report.output_amount(OutputKind::Recipient),
+        Amount::from_btc(20.0).unwrap()
+    );
+    assert!(report.output_amount(OutputKind::Change) > Amount::ZERO);
+    assert!(report.fee < SignedAmount::ZERO);
+    assert_eq!(
+        report.input_amount(),
+        report.output_amount(OutputKind::Recipient)
+            + report.output_amount(OutputKind::Change)
+            + report.fee.abs().to_unsigned().unwrap()
+    );
+
+    // 101 blocks to fund the Miner plus the confirming block
+    assert_eq!(report.block_height, 102);
+
+    // The Miner wallet agrees on block, fee and outputs
+    let miner = node.loaded_wallet("Miner").unwrap();
+    let tx = miner
+        .client()
+        .get_transaction(&report.txid, Some(true))
+        .unwrap();
+    assert_eq!(tx.info.blockhash, Some(report.block_hash));
+    assert_eq!(tx.info.blockheight, Some(report.block_height as u32));
+    assert_eq!(tx.fee, Some(report.fee));
+    let decoded = tx.transaction().unwrap();
+    assert_eq!(decoded.input.len(), 1);
+    assert_eq!(decoded.output.len(), 2);
+    for output in &report.outputs {
+        let onchain = &decoded.output[output.vout as usize];
+        assert_eq!(onchain.value, output.amount);
+    }
+}